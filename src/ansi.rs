@@ -0,0 +1,322 @@
+use std::borrow::Cow;
+use std::fmt;
+
+const ESC: u8 = 0x1b;
+const BEL: u8 = 0x07;
+
+/// A terminal color, as set by SGR escape sequences.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Color {
+    /// One of the 256 indexed colors: 0-7 are the basic colors, 8-15 their bright variants.
+    Fixed(u8),
+    /// A 24-bit color.
+    Rgb(u8, u8, u8),
+}
+
+const NAMES: [&str; 8] = [
+    "black", "red", "green", "yellow", "blue", "magenta", "cyan", "white",
+];
+
+impl fmt::Display for Color {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Color::Fixed(n) if n < 8 => write!(f, "{}", NAMES[n as usize]),
+            Color::Fixed(n) if n < 16 => write!(f, "bright {}", NAMES[n as usize - 8]),
+            Color::Fixed(n) => write!(f, "color {}", n),
+            Color::Rgb(r, g, b) => write!(f, "#{:02x}{:02x}{:02x}", r, g, b),
+        }
+    }
+}
+
+/// The style of text, as set by SGR escape sequences.
+///
+/// Used as an expectation, a style only requires the attributes it sets: `Style::red()` matches
+/// bold red text too.
+///
+/// # Examples
+///
+/// ```rust
+/// extern crate assert_cli;
+/// use assert_cli::{Assert, Style};
+///
+/// Assert::command(&["printf", "\\033[1;31merror\\033[0m: bad input"])
+///     .stdout().styled_contains("error", Style::red().bold())
+///     .stdout().stripped().is("error: bad input")
+///     .unwrap();
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Style {
+    fg: Option<Color>,
+    bg: Option<Color>,
+    bold: bool,
+    dim: bool,
+    italic: bool,
+    underline: bool,
+    reverse: bool,
+}
+
+macro_rules! color_constructors {
+    ($($name:ident = $n:expr),*) => {
+        $(
+            #[doc = concat!("Text in ", stringify!($name), ".")]
+            pub fn $name() -> Self {
+                Self::new().fg(Color::Fixed($n))
+            }
+        )*
+    }
+}
+
+impl Style {
+    /// Unstyled text, matching any text as an expectation.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    color_constructors!(
+        black = 0,
+        red = 1,
+        green = 2,
+        yellow = 3,
+        blue = 4,
+        magenta = 5,
+        cyan = 6,
+        white = 7
+    );
+
+    /// Set the foreground color.
+    pub fn fg(mut self, color: Color) -> Self {
+        self.fg = Some(color);
+        self
+    }
+
+    /// Set the background color.
+    pub fn on(mut self, color: Color) -> Self {
+        self.bg = Some(color);
+        self
+    }
+
+    /// Make the text bold.
+    pub fn bold(mut self) -> Self {
+        self.bold = true;
+        self
+    }
+
+    /// Make the text dim.
+    pub fn dim(mut self) -> Self {
+        self.dim = true;
+        self
+    }
+
+    /// Make the text italic.
+    pub fn italic(mut self) -> Self {
+        self.italic = true;
+        self
+    }
+
+    /// Underline the text.
+    pub fn underline(mut self) -> Self {
+        self.underline = true;
+        self
+    }
+
+    /// Swap foreground and background.
+    pub fn reverse(mut self) -> Self {
+        self.reverse = true;
+        self
+    }
+
+    /// Whether `self` has everything `expected` sets.
+    pub(crate) fn satisfies(&self, expected: &Style) -> bool {
+        (expected.fg.is_none() || self.fg == expected.fg)
+            && (expected.bg.is_none() || self.bg == expected.bg)
+            && (self.bold || !expected.bold)
+            && (self.dim || !expected.dim)
+            && (self.italic || !expected.italic)
+            && (self.underline || !expected.underline)
+            && (self.reverse || !expected.reverse)
+    }
+
+    /// Apply the parameters of an SGR sequence.
+    fn apply(&mut self, params: &[u8]) {
+        let mut params = params.split(|&b| b == b';' || b == b':').map(|p| {
+            p.iter().fold(0u32, |n, &d| {
+                n.saturating_mul(10).saturating_add(u32::from(d - b'0'))
+            })
+        });
+        while let Some(param) = params.next() {
+            match param {
+                0 => *self = Style::default(),
+                1 => self.bold = true,
+                2 => self.dim = true,
+                3 => self.italic = true,
+                4 => self.underline = true,
+                7 => self.reverse = true,
+                22 => {
+                    self.bold = false;
+                    self.dim = false;
+                }
+                23 => self.italic = false,
+                24 => self.underline = false,
+                27 => self.reverse = false,
+                30..=37 => self.fg = Some(Color::Fixed(param as u8 - 30)),
+                38 => self.fg = extended_color(&mut params),
+                39 => self.fg = None,
+                40..=47 => self.bg = Some(Color::Fixed(param as u8 - 40)),
+                48 => self.bg = extended_color(&mut params),
+                49 => self.bg = None,
+                90..=97 => self.fg = Some(Color::Fixed(param as u8 - 90 + 8)),
+                100..=107 => self.bg = Some(Color::Fixed(param as u8 - 100 + 8)),
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Parse the rest of a `38;5;n` or `38;2;r;g;b` color.
+fn extended_color<I: Iterator<Item = u32>>(params: &mut I) -> Option<Color> {
+    let byte = |n: Option<u32>| n.unwrap_or(0).min(255) as u8;
+    match params.next() {
+        Some(5) => Some(Color::Fixed(byte(params.next()))),
+        Some(2) => Some(Color::Rgb(
+            byte(params.next()),
+            byte(params.next()),
+            byte(params.next()),
+        )),
+        _ => None,
+    }
+}
+
+impl fmt::Display for Style {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut words = vec![];
+        let attributes = [
+            (self.bold, "bold"),
+            (self.dim, "dim"),
+            (self.italic, "italic"),
+            (self.underline, "underlined"),
+            (self.reverse, "reversed"),
+        ];
+        words.extend(attributes.iter().filter(|a| a.0).map(|a| a.1.to_owned()));
+        if let Some(fg) = self.fg {
+            words.push(fg.to_string());
+        }
+        if let Some(bg) = self.bg {
+            words.push(format!("on {}", bg));
+        }
+        if words.is_empty() {
+            write!(f, "unstyled")
+        } else {
+            write!(f, "{}", words.join(" "))
+        }
+    }
+}
+
+/// Output split into its text and the style of each byte of text.
+#[derive(Debug)]
+pub(crate) struct Styled {
+    pub text: Vec<u8>,
+    pub styles: Vec<Style>,
+}
+
+/// Parse the escape sequences in `bytes`, keeping SGR styles and dropping everything else.
+pub(crate) fn parse(bytes: &[u8]) -> Styled {
+    let mut styled = Styled {
+        text: Vec::with_capacity(bytes.len()),
+        styles: Vec::with_capacity(bytes.len()),
+    };
+    let mut style = Style::default();
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] != ESC {
+            styled.text.push(bytes[i]);
+            styled.styles.push(style);
+            i += 1;
+            continue;
+        }
+        match bytes.get(i + 1) {
+            // CSI: parameters, intermediates, and a final byte.
+            Some(&b'[') => {
+                let start = i + 2;
+                let end = bytes[start..]
+                    .iter()
+                    .position(|b| (0x40..=0x7e).contains(b))
+                    .map_or(bytes.len(), |p| start + p);
+                if bytes.get(end) == Some(&b'm')
+                    && bytes[start..end]
+                        .iter()
+                        .all(|&b| b.is_ascii_digit() || b == b';' || b == b':')
+                {
+                    style.apply(&bytes[start..end]);
+                }
+                i = end + 1;
+            }
+            // OSC, like hyperlinks: until BEL or ST.
+            Some(&b']') => {
+                let mut end = i + 2;
+                while end < bytes.len() {
+                    if bytes[end] == BEL {
+                        end += 1;
+                        break;
+                    }
+                    if bytes[end] == ESC && bytes.get(end + 1) == Some(&b'\\') {
+                        end += 2;
+                        break;
+                    }
+                    end += 1;
+                }
+                i = end;
+            }
+            _ => i += 2,
+        }
+    }
+    styled
+}
+
+/// `bytes` without escape sequences.
+pub(crate) fn strip(bytes: &[u8]) -> Cow<'_, [u8]> {
+    if bytes.contains(&ESC) {
+        Cow::Owned(parse(bytes).text)
+    } else {
+        Cow::Borrowed(bytes)
+    }
+}
+
+/// Where the first escape sequence starts, if any.
+pub(crate) fn find_escape(bytes: &[u8]) -> Option<usize> {
+    bytes.iter().position(|&b| b == ESC)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parses_sgr() {
+        let styled = parse(b"a\x1b[1;31mb\x1b[22mc\x1b[38;5;208;48;2;1;2;3md\x1b[0me");
+        assert_eq!(styled.text, b"abcde");
+        assert_eq!(styled.styles[0], Style::new());
+        assert_eq!(styled.styles[1], Style::red().bold());
+        assert_eq!(styled.styles[2], Style::red());
+        assert_eq!(
+            styled.styles[3],
+            Style::red().fg(Color::Fixed(208)).on(Color::Rgb(1, 2, 3))
+        );
+        assert_eq!(styled.styles[4], Style::new());
+        assert_eq!(styled.styles[3].to_string(), "color 208 on #010203");
+    }
+
+    #[test]
+    fn strips_other_sequences() {
+        let link = b"\x1b]8;;http://example.com\x07link\x1b]8;;\x1b\\ \x1b[2Kdone\x1b[K";
+        assert_eq!(&*strip(link), b"link done");
+    }
+
+    #[test]
+    fn expected_style_is_a_subset() {
+        let got = Style::red().bold().underline();
+        assert!(got.satisfies(&Style::red().bold()));
+        assert!(got.satisfies(&Style::new()));
+        assert!(!got.satisfies(&Style::green()));
+        assert!(!Style::red().satisfies(&Style::red().bold()));
+    }
+}
@@ -1,16 +1,40 @@
 use std::default;
 use std::ffi::{OsStr, OsString};
-use std::io::Write;
+use std::io::{self, Read, Write};
+use std::mem;
+use std::ops::RangeBounds;
 use std::path::PathBuf;
-use std::process::{Command, Stdio};
+use std::process::{self, Command, Stdio};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
 use std::vec::Vec;
 
 use environment::Environment;
 use failure;
 use failure::Fail;
+use tempfile::{self, TempDir};
 
+use ansi::Style;
+use capture::{self, Captured, Limit, Watch};
+use color::ColorChoice;
+use determinism::{self, Snapshot};
+use diff::{DiffStyle, Renderer};
 use errors::*;
-use output::{Content, Output, OutputKind, OutputPredicate};
+use exit_code::{ExitCodeNames, ExpectedCode};
+use golden::{self, GoldenDir};
+use help::Help;
+use invocation::Invocation;
+use output::{Content, Output, OutputError, OutputKind, OutputPredicate, OutputTooLargeError};
+use pty::{Pty, Tty};
+use rlimit::{self, RLimit};
+use rusage::ResourceUsage;
+use sandbox::{Base, Sandbox, Seed};
+use shell::{self, ParseCommandError};
+use stdin::Stdin;
+
+/// How deep `Assert::help_recursive` follows subcommands.
+const MAX_HELP_DEPTH: usize = 8;
 
 /// Assertions for a specific command.
 #[derive(Debug)]
@@ -20,9 +44,34 @@ pub struct Assert {
     env: Environment,
     current_dir: Option<PathBuf>,
     expect_success: Option<bool>,
-    expect_exit_code: Option<i32>,
+    expect_exit_code: Option<ExpectedCode>,
+    exit_code_names: ExitCodeNames,
     expect_output: Vec<OutputPredicate>,
-    stdin_contents: Option<Vec<u8>>,
+    expect_dirs: Vec<GoldenDir>,
+    stdin: Stdin,
+    output_limit: Limit,
+    expect_max_duration: Option<Duration>,
+    expect_min_duration: Option<Duration>,
+    expect_max_rss: Option<u64>,
+    expect_max_cpu_time: Option<Duration>,
+    rlimits: Vec<(RLimit, u64)>,
+    tty: Option<Tty>,
+    hermetic: bool,
+    seeds: Vec<Seed>,
+    show_invocation: bool,
+    /// A directory the arguments refer to, see `CommandTemplate`.
+    tmpdir: Option<TempDir>,
+    /// The environment `with_env` adds to, see `CommandTemplate`.
+    template_env: Option<Environment>,
+    /// Why the `Assert` couldn't be derived from a `CommandTemplate`, reported when executed.
+    setup_error: Option<failure::Error>,
+    retries: u32,
+    backoff: Duration,
+    flaky_runs: Option<usize>,
+    deterministic_runs: Option<usize>,
+    deterministic_files: bool,
+    diff_style: DiffStyle,
+    color: ColorChoice,
 }
 
 impl default::Default for Assert {
@@ -45,8 +94,30 @@ impl default::Default for Assert {
             current_dir: None,
             expect_success: Some(true),
             expect_exit_code: None,
+            exit_code_names: ExitCodeNames::default(),
             expect_output: vec![],
-            stdin_contents: None,
+            expect_dirs: vec![],
+            stdin: Stdin::default(),
+            output_limit: Limit::default(),
+            expect_max_duration: None,
+            expect_min_duration: None,
+            expect_max_rss: None,
+            expect_max_cpu_time: None,
+            rlimits: vec![],
+            tty: None,
+            hermetic: false,
+            seeds: vec![],
+            show_invocation: false,
+            tmpdir: None,
+            template_env: None,
+            setup_error: None,
+            retries: 0,
+            backoff: Duration::from_secs(0),
+            flaky_runs: None,
+            deterministic_runs: None,
+            deterministic_files: false,
+            diff_style: DiffStyle::default(),
+            color: ColorChoice::default(),
         }
     }
 }
@@ -120,6 +191,85 @@ impl Assert {
         }
     }
 
+    /// Run a command given as a shell command line, without invoking a shell.
+    ///
+    /// The command is split into words like a POSIX shell would: with single and double quotes,
+    /// backslash escapes and `$NAME` or `${NAME}` replaced by the variable of the current
+    /// process' environment.  Pipes, redirections and other syntax that needs a shell are
+    /// rejected, as are unbalanced quotes.
+    ///
+    /// Defaults to asserting _successful_ execution.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// extern crate assert_cli;
+    ///
+    /// assert_cli::Assert::parse_command(r#"echo --name "John Doe" --flag='a b'"#)
+    ///     .unwrap()
+    ///     .stdout().is("--name John Doe --flag=a b")
+    ///     .unwrap();
+    ///
+    /// assert!(assert_cli::Assert::parse_command("echo 'John").is_err());
+    /// ```
+    pub fn parse_command(command: &str) -> Result<Self, ParseCommandError> {
+        Self::parse_command_with_env(command, Environment::inherit())
+    }
+
+    /// Run a command given as a shell command line, with environment `env`.
+    ///
+    /// Like [`parse_command`](#method.parse_command), but variables are looked up in `env`,
+    /// which is also the command's environment.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// extern crate assert_cli;
+    /// use assert_cli::{Assert, Environment};
+    ///
+    /// let env = Environment::inherit().insert("NAME", "John Doe");
+    /// Assert::parse_command_with_env("sh -c 'echo \"$0, $NAME\"' \"$NAME\"", env)
+    ///     .unwrap()
+    ///     .stdout().is("John Doe, John Doe")
+    ///     .unwrap();
+    /// ```
+    pub fn parse_command_with_env<E: Into<Environment>>(
+        command: &str,
+        env: E,
+    ) -> Result<Self, ParseCommandError> {
+        let env = env.into();
+        let cmd = shell::split(command, &env.clone().compile())?;
+        Ok(Self::command(&cmd).with_env(env))
+    }
+
+    pub(crate) fn cmd(&self) -> &[OsString] {
+        &self.cmd
+    }
+
+    /// How long to wait before the first retry.
+    pub(crate) fn backoff(&self) -> Duration {
+        self.backoff
+    }
+
+    /// Start from `env`, which later calls to `with_env` add to.
+    pub(crate) fn extend_env(mut self, env: &Environment) -> Self {
+        self.env = env.clone();
+        self.template_env = Some(env.clone());
+        self
+    }
+
+    /// Fail with `err` once executed.
+    pub(crate) fn setup_failed(mut self, err: failure::Error) -> Self {
+        self.setup_error = Some(err);
+        self
+    }
+
+    /// Remove `dir` only once the `Assert` is dropped.
+    pub(crate) fn keep_alive(mut self, dir: TempDir) -> Self {
+        self.tmpdir = Some(dir);
+        self
+    }
+
     /// Add arguments to the command.
     ///
     /// # Examples
@@ -151,7 +301,104 @@ impl Assert {
     ///     .unwrap();
     /// ```
     pub fn stdin<S: Into<Vec<u8>>>(mut self, contents: S) -> Self {
-        self.stdin_contents = Some(contents.into());
+        self.stdin = Stdin::Bytes(contents.into());
+        self
+    }
+
+    /// Feed the contents of a file to the command's stdin.
+    ///
+    /// A relative `path` is resolved against the [`current_dir`](#method.current_dir), when set.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// extern crate assert_cli;
+    ///
+    /// assert_cli::Assert::command(&["cat"])
+    ///     .stdin_file("Cargo.toml")
+    ///     .stdout().contains("assert_cli")
+    ///     .unwrap();
+    /// ```
+    pub fn stdin_file<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        self.stdin = Stdin::File(path.into());
+        self
+    }
+
+    /// Stream the command's stdin from `reader`.
+    ///
+    /// The input is written while the command's output is captured, so it may exceed what the
+    /// pipes can buffer.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// extern crate assert_cli;
+    ///
+    /// assert_cli::Assert::command(&["cat"])
+    ///     .stdin_reader(std::io::Cursor::new("42"))
+    ///     .stdout().is("42")
+    ///     .unwrap();
+    /// ```
+    pub fn stdin_reader<R: Read + Send + 'static>(mut self, reader: R) -> Self {
+        self.stdin = Stdin::Reader(Box::new(reader));
+        self
+    }
+
+    /// Generate the command's stdin by writing to the pipe.
+    ///
+    /// The generator runs on its own thread while the command's output is captured.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// extern crate assert_cli;
+    ///
+    /// assert_cli::Assert::command(&["wc", "-l"])
+    ///     .stdin_with(|stdin| {
+    ///         for i in 0..1000 {
+    ///             writeln!(stdin, "line {}", i)?;
+    ///         }
+    ///         Ok(())
+    ///     })
+    ///     .stdout().contains("1000")
+    ///     .unwrap();
+    /// ```
+    pub fn stdin_with<F>(mut self, generator: F) -> Self
+    where
+        F: Fn(&mut dyn Write) -> io::Result<()> + Send + Sync + 'static,
+    {
+        self.stdin = Stdin::Generator(Arc::new(generator));
+        self
+    }
+
+    /// Give the command a stdin pipe that is closed right away.
+    ///
+    /// This is the default when no stdin is set.
+    pub fn stdin_closed(mut self) -> Self {
+        self.stdin = Stdin::Closed;
+        self
+    }
+
+    /// Let the command read from the test process's stdin.
+    pub fn stdin_inherited(mut self) -> Self {
+        self.stdin = Stdin::Inherit;
+        self
+    }
+
+    /// Connect the command's stdin to the null device.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// extern crate assert_cli;
+    ///
+    /// assert_cli::Assert::command(&["cat"])
+    ///     .stdin_null()
+    ///     .stdout().is("")
+    ///     .unwrap();
+    /// ```
+    pub fn stdin_null(mut self) -> Self {
+        self.stdin = Stdin::Null;
         self
     }
 
@@ -173,8 +420,79 @@ impl Assert {
         self
     }
 
+    /// Run the command in a throwaway home, so it can't pick up the developer's configuration.
+    ///
+    /// Each execution gets fresh `HOME`, `XDG_CONFIG_HOME`, `XDG_CACHE_HOME`, `XDG_DATA_HOME`
+    /// and `TMPDIR` directories, which are removed afterwards.  `LANG` and `LC_ALL` are set to
+    /// `C`, `TZ` to `UTC` and `TERM` to `dumb`, while other `LC_*`, `LANGUAGE`, `COLORTERM`,
+    /// `COLUMNS`, `LINES` and `CLICOLOR_FORCE` are removed.  These override the environment set
+    /// with [`with_env`](#method.with_env).
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// extern crate assert_cli;
+    ///
+    /// assert_cli::Assert::command(&["sh", "-c", "ls -A \"$HOME\"/.config; echo $TZ"])
+    ///     .hermetic()
+    ///     .stdout().is("UTC")
+    ///     .unwrap();
+    /// ```
+    pub fn hermetic(mut self) -> Self {
+        self.hermetic = true;
+        self
+    }
+
+    /// Create a file relative to the sandboxed `HOME` before the command runs.
+    ///
+    /// Implies [`hermetic`](#method.hermetic).
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// extern crate assert_cli;
+    ///
+    /// assert_cli::Assert::command(&["sh", "-c", "cat \"$HOME/.netrc\""])
+    ///     .home_file(".netrc", "machine example.com")
+    ///     .stdout().is("machine example.com")
+    ///     .unwrap();
+    /// ```
+    pub fn home_file<P: Into<PathBuf>, C: Into<Vec<u8>>>(mut self, path: P, contents: C) -> Self {
+        self.seeds.push(Seed {
+            base: Base::Home,
+            path: path.into(),
+            contents: contents.into(),
+        });
+        self.hermetic()
+    }
+
+    /// Create a file relative to the sandboxed `XDG_CONFIG_HOME` before the command runs.
+    ///
+    /// Implies [`hermetic`](#method.hermetic).
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// extern crate assert_cli;
+    ///
+    /// assert_cli::Assert::command(&["sh", "-c", "cat ~/.config/ourtool/config.toml"])
+    ///     .config_file("ourtool/config.toml", "verbose = true")
+    ///     .stdout().is("verbose = true")
+    ///     .unwrap();
+    /// ```
+    pub fn config_file<P: Into<PathBuf>, C: Into<Vec<u8>>>(mut self, path: P, contents: C) -> Self {
+        self.seeds.push(Seed {
+            base: Base::Config,
+            path: path.into(),
+            contents: contents.into(),
+        });
+        self.hermetic()
+    }
+
     /// Sets environments variables for the command.
     ///
+    /// For an `Assert` derived from a [`CommandTemplate`], they are added to the template's.
+    ///
     /// # Examples
     ///
     /// ```rust
@@ -202,9 +520,101 @@ impl Assert {
     ///     .execute()
     ///     .unwrap();
     /// ```
+    ///
+    /// [`CommandTemplate`]: struct.CommandTemplate.html
     pub fn with_env<E: Into<Environment>>(mut self, env: E) -> Self {
-        self.env = env.into();
+        let env = env.into();
+        self.env = match self.template_env {
+            Some(ref base) => env
+                .compile()
+                .into_iter()
+                .fold(base.clone(), |base, (key, value)| base.insert(key, value)),
+            None => env,
+        };
+
+        self
+    }
+
+    /// Stop capturing stdout and stderr beyond `max` bytes each.
+    ///
+    /// The command is still allowed to run to completion, with the rest of its output discarded,
+    /// unless [`kill_on_output_limit`] is set.  Either way the assertion fails.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// extern crate assert_cli;
+    ///
+    /// let x = assert_cli::Assert::command(&["seq", "1000000"])
+    ///     .max_output_bytes(1024)
+    ///     .execute();
+    /// assert!(x.is_err());
+    /// ```
+    ///
+    /// [`kill_on_output_limit`]: #method.kill_on_output_limit
+    pub fn max_output_bytes(mut self, max: usize) -> Self {
+        self.output_limit.max_bytes = Some(max);
+        self
+    }
+
+    /// Kill the command as soon as it exceeds [`max_output_bytes`].
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// extern crate assert_cli;
+    ///
+    /// let x = assert_cli::Assert::command(&["yes"])
+    ///     .max_output_bytes(1024)
+    ///     .kill_on_output_limit()
+    ///     .execute();
+    /// assert!(x.is_err());
+    /// ```
+    ///
+    /// [`max_output_bytes`]: #method.max_output_bytes
+    pub fn kill_on_output_limit(mut self) -> Self {
+        self.output_limit.kill = true;
+        self
+    }
+
+    /// Sets how mismatching output is rendered in assertion failures.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// extern crate assert_cli;
+    ///
+    /// let x = assert_cli::Assert::command(&["seq", "100"])
+    ///     .diff_style(assert_cli::DiffStyle::Unified { context: 2 })
+    ///     .stdout().is("1\n2\n3")
+    ///     .execute();
+    /// assert!(x.is_err());
+    /// ```
+    pub fn diff_style(mut self, style: DiffStyle) -> Self {
+        self.diff_style = style;
+        self
+    }
 
+    /// Sets when assertion failures are colored.
+    ///
+    /// Defaults to [`ColorChoice::Auto`], which colors only when stderr is a terminal and
+    /// honors `NO_COLOR` and `CLICOLOR_FORCE`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// extern crate assert_cli;
+    ///
+    /// let x = assert_cli::Assert::command(&["echo", "42"])
+    ///     .color(assert_cli::ColorChoice::Never)
+    ///     .stdout().is("1337")
+    ///     .execute();
+    /// assert!(x.is_err());
+    /// ```
+    ///
+    /// [`ColorChoice::Auto`]: enum.ColorChoice.html#variant.Auto
+    pub fn color(mut self, choice: ColorChoice) -> Self {
+        self.color = choice;
         self
     }
 
@@ -278,53 +688,101 @@ impl Assert {
     /// ```
     pub fn fails_with(mut self, expect_exit_code: i32) -> Self {
         self.expect_success = Some(false);
-        self.expect_exit_code = Some(expect_exit_code);
+        self.expect_exit_code = Some(ExpectedCode::Exactly(expect_exit_code));
         self
     }
 
-    /// Do not care whether the command exits successfully or if it fails.
+    /// Expect the command to fail and return the code named `name`.
     ///
-    /// This function removes any assertions that were already set, including
-    /// any expected exit code that was set with [`fails_with`].
+    /// Names are looked up in the [`exit_code_names`](#method.exit_code_names), `sysexits.h` by
+    /// default, before the command runs, failing without running it for an unknown name.
     ///
     /// # Examples
     ///
     /// ```rust
     /// extern crate assert_cli;
     ///
-    /// assert_cli::Assert::command(&["cat", "non-existing-file"])
-    ///     .ignore_status()
-    ///     .and()
-    ///     .stderr().is("cat: non-existing-file: No such file or directory")
+    /// assert_cli::Assert::command(&["sh", "-c", "exit 64"])
+    ///     .fails_with_named("EX_USAGE")
     ///     .unwrap();
     /// ```
+    pub fn fails_with_named<N: Into<String>>(mut self, name: N) -> Self {
+        self.expect_success = Some(false);
+        self.expect_exit_code = Some(ExpectedCode::Named(name.into()));
+        self
+    }
+
+    /// Name exit codes, for [`fails_with_named`](#method.fails_with_named) and failure messages.
     ///
-    /// [`fails_with`]: #method.fails_with
-    pub fn ignore_status(mut self) -> Self {
-        self.expect_exit_code = None;
+    /// Replaces the default names from `sysexits.h`.
+    pub fn exit_code_names(mut self, names: ExitCodeNames) -> Self {
+        self.exit_code_names = names;
+        self
+    }
+
+    /// Expect the command to exit with a code in `range`.
+    ///
+    /// Whether the command succeeded is not checked on its own, so the range may include 0.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// extern crate assert_cli;
+    ///
+    /// assert_cli::Assert::command(&["sh", "-c", "exit 3"])
+    ///     .exit_code_in(2..=9)
+    ///     .unwrap();
+    /// ```
+    pub fn exit_code_in<R: RangeBounds<i32>>(mut self, range: R) -> Self {
         self.expect_success = None;
+        self.expect_exit_code = Some(ExpectedCode::range(&range));
         self
     }
 
-    /// Create an assertion for stdout's contents
+    /// Expect the command to exit with one of `codes`.
+    ///
+    /// Whether the command succeeded is not checked on its own, so `codes` may include 0.
     ///
     /// # Examples
     ///
     /// ```rust
     /// extern crate assert_cli;
     ///
-    /// assert_cli::Assert::command(&["echo", "42"])
-    ///     .stdout().contains("42")
+    /// assert_cli::Assert::command(&["grep", "42"])
+    ///     .stdin("43")
+    ///     .exit_code_one_of(&[0, 1])
     ///     .unwrap();
     /// ```
-    pub fn stdout(self) -> OutputAssertionBuilder {
-        OutputAssertionBuilder {
-            assertion: self,
-            kind: OutputKind::StdOut,
-        }
+    pub fn exit_code_one_of(mut self, codes: &[i32]) -> Self {
+        self.expect_success = None;
+        self.expect_exit_code = Some(ExpectedCode::OneOf(codes.to_vec()));
+        self
     }
 
-    /// Create an assertion for stdout's contents
+    /// Expect the command to exit with any code but `code`.
+    ///
+    /// Whether the command succeeded is not checked on its own.  A command killed by a signal
+    /// has no exit code, and fails this assertion.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// extern crate assert_cli;
+    ///
+    /// assert_cli::Assert::command(&["false"])
+    ///     .exit_code_not(2)
+    ///     .unwrap();
+    /// ```
+    pub fn exit_code_not(mut self, code: i32) -> Self {
+        self.expect_success = None;
+        self.expect_exit_code = Some(ExpectedCode::Not(code));
+        self
+    }
+
+    /// Do not care whether the command exits successfully or if it fails.
+    ///
+    /// This function removes any assertions that were already set, including
+    /// any expected exit code that was set with [`fails_with`].
     ///
     /// # Examples
     ///
@@ -332,96 +790,859 @@ impl Assert {
     /// extern crate assert_cli;
     ///
     /// assert_cli::Assert::command(&["cat", "non-existing-file"])
-    ///     .fails_with(1)
+    ///     .ignore_status()
     ///     .and()
     ///     .stderr().is("cat: non-existing-file: No such file or directory")
     ///     .unwrap();
     /// ```
-    pub fn stderr(self) -> OutputAssertionBuilder {
-        OutputAssertionBuilder {
-            assertion: self,
-            kind: OutputKind::StdErr,
-        }
+    ///
+    /// [`fails_with`]: #method.fails_with
+    pub fn ignore_status(mut self) -> Self {
+        self.expect_exit_code = None;
+        self.expect_success = None;
+        self
     }
 
-    /// Execute the command and check the assertions.
+    /// Expect the command to finish within `limit`.
+    ///
+    /// The command is terminated once it runs longer than that.
     ///
     /// # Examples
     ///
     /// ```rust
     /// extern crate assert_cli;
+    /// use std::time::Duration;
     ///
-    /// let test = assert_cli::Assert::command(&["echo", "42"])
+    /// assert_cli::Assert::command(&["echo", "42"])
+    ///     .completes_within(Duration::from_secs(10))
+    ///     .unwrap();
+    ///
+    /// let x = assert_cli::Assert::command(&["sleep", "60"])
+    ///     .completes_within(Duration::from_millis(100))
+    ///     .execute();
+    /// assert!(x.is_err());
+    /// ```
+    pub fn completes_within(mut self, limit: Duration) -> Self {
+        self.expect_max_duration = Some(limit);
+        self
+    }
+
+    /// Expect the command to run for at least `limit`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// extern crate assert_cli;
+    /// use std::time::Duration;
+    ///
+    /// assert_cli::Assert::command(&["sleep", "0.1"])
+    ///     .takes_at_least(Duration::from_millis(100))
+    ///     .unwrap();
+    /// ```
+    pub fn takes_at_least(mut self, limit: Duration) -> Self {
+        self.expect_min_duration = Some(limit);
+        self
+    }
+
+    /// Expect the command's peak resident set size to stay below `bytes`.
+    ///
+    /// Resource usage is only collected on Linux; elsewhere this assertion fails.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// extern crate assert_cli;
+    ///
+    /// # if cfg!(target_os = "linux") {
+    /// assert_cli::Assert::command(&["echo", "42"])
+    ///     .max_rss_below(1024 * 1024 * 1024)
+    ///     .unwrap();
+    /// # }
+    /// ```
+    pub fn max_rss_below(mut self, bytes: u64) -> Self {
+        self.expect_max_rss = Some(bytes);
+        self
+    }
+
+    /// Expect the command's CPU time, user and system, to stay below `limit`.
+    ///
+    /// Resource usage is only collected on Linux; elsewhere this assertion fails.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// extern crate assert_cli;
+    /// use std::time::Duration;
+    ///
+    /// # if cfg!(target_os = "linux") {
+    /// assert_cli::Assert::command(&["echo", "42"])
+    ///     .cpu_time_below(Duration::from_secs(10))
+    ///     .unwrap();
+    /// # }
+    /// ```
+    pub fn cpu_time_below(mut self, limit: Duration) -> Self {
+        self.expect_max_cpu_time = Some(limit);
+        self
+    }
+
+    /// Set a resource limit on the command, replacing an earlier one for the same resource.
+    ///
+    /// When the command is killed by a signal that hitting a limit sends, failures explain
+    /// which limit it likely hit.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// extern crate assert_cli;
+    /// use assert_cli::RLimit;
+    ///
+    /// # if cfg!(unix) {
+    /// assert_cli::Assert::command(&["echo", "42"])
+    ///     .rlimit(RLimit::OpenFiles, 64)
+    ///     .rlimit(RLimit::CoreSize, 0)
+    ///     .unwrap();
+    /// # }
+    /// ```
+    pub fn rlimit(mut self, resource: RLimit, value: u64) -> Self {
+        self.rlimits.retain(|&(r, _)| r != resource);
+        self.rlimits.push((resource, value));
+        self
+    }
+
+    /// Limit the number of file descriptors the command can open.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// extern crate assert_cli;
+    ///
+    /// # if cfg!(unix) {
+    /// assert_cli::Assert::command(&["echo", "42"])
+    ///     .limit_open_files(16)
+    ///     .stdout().contains("42")
+    ///     .unwrap();
+    /// # }
+    /// ```
+    pub fn limit_open_files(self, count: u64) -> Self {
+        self.rlimit(RLimit::OpenFiles, count)
+    }
+
+    /// Limit the command's virtual address space, in bytes.
+    pub fn limit_address_space(self, bytes: u64) -> Self {
+        self.rlimit(RLimit::AddressSpace, bytes)
+    }
+
+    /// Limit the CPU time the command can use, in whole seconds.
+    ///
+    /// The command is sent `SIGXCPU` once it used them up.
+    pub fn limit_cpu_seconds(self, seconds: u64) -> Self {
+        self.rlimit(RLimit::CpuTime, seconds)
+    }
+
+    /// Keep the command from writing core dumps.
+    pub fn no_core_dumps(self) -> Self {
+        self.rlimit(RLimit::CoreSize, 0)
+    }
+
+    /// Attach stdout and stderr to terminals of `rows` by `cols`, for commands that format
+    /// their output differently for terminals, like with colors, progress bars or wrapping.
+    ///
+    /// `COLUMNS` and `LINES` are set to match, overriding [`hermetic`](#method.hermetic).
+    /// Output is read through the terminals, so the usual assertions apply to it.  Newlines
+    /// are kept as they are instead of becoming `\r\n`.  Only supported on unix.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// extern crate assert_cli;
+    ///
+    /// # if cfg!(unix) {
+    /// assert_cli::Assert::command(&["sh", "-c", "test -t 1 && test -t 2 && echo $COLUMNS"])
+    ///     .tty(24, 100)
+    ///     .stdout().is("100")
+    ///     .unwrap();
+    /// # }
+    /// ```
+    pub fn tty(mut self, rows: u16, cols: u16) -> Self {
+        self.tty = Some(Tty {
+            rows,
+            cols,
+            stdout: true,
+            stderr: true,
+        });
+        self
+    }
+
+    /// Attach only stdout to a terminal of `rows` by `cols`, stderr stays a pipe.
+    ///
+    /// See [`tty`](#method.tty).
+    pub fn tty_stdout(mut self, rows: u16, cols: u16) -> Self {
+        self.tty = Some(Tty {
+            rows,
+            cols,
+            stdout: true,
+            stderr: false,
+        });
+        self
+    }
+
+    /// Attach only stderr to a terminal of `rows` by `cols`, stdout stays a pipe.
+    ///
+    /// See [`tty`](#method.tty).
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// extern crate assert_cli;
+    ///
+    /// # if cfg!(unix) {
+    /// assert_cli::Assert::command(&["sh", "-c", "test -t 2 && ! test -t 1 && echo progress >&2"])
+    ///     .tty_stderr(24, 80)
+    ///     .stderr().is("progress")
+    ///     .unwrap();
+    /// # }
+    /// ```
+    pub fn tty_stderr(mut self, rows: u16, cols: u16) -> Self {
+        self.tty = Some(Tty {
+            rows,
+            cols,
+            stdout: false,
+            stderr: true,
+        });
+        self
+    }
+
+    /// Create an assertion for a directory the command writes, relative to `current_dir`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// extern crate assert_cli;
+    /// extern crate tempfile;
+    /// use std::fs;
+    ///
+    /// let expected = tempfile::tempdir().unwrap();
+    /// fs::create_dir(expected.path().join("src")).unwrap();
+    /// fs::write(expected.path().join("src/main.rs"), "fn main() {}\n").unwrap();
+    ///
+    /// let work = tempfile::tempdir().unwrap();
+    /// assert_cli::Assert::command(&["sh", "-c", "mkdir -p out/src; echo 'fn main() {}' > out/src/main.rs"])
+    ///     .current_dir(work.path())
+    ///     .dir("out").matches(expected.path())
+    ///     .unwrap();
+    /// ```
+    pub fn dir<P: Into<PathBuf>>(self, path: P) -> DirAssertionBuilder {
+        DirAssertionBuilder {
+            assertion: self,
+            actual: path.into(),
+            permissions: false,
+            symlinks: false,
+        }
+    }
+
+    /// Create an assertion for stdout's contents
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// extern crate assert_cli;
+    ///
+    /// assert_cli::Assert::command(&["echo", "42"])
+    ///     .stdout().contains("42")
+    ///     .unwrap();
+    /// ```
+    pub fn stdout(self) -> OutputAssertionBuilder {
+        OutputAssertionBuilder {
+            assertion: self,
+            kind: OutputKind::StdOut,
+            stripped: false,
+        }
+    }
+
+    /// Create an assertion for stdout's contents
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// extern crate assert_cli;
+    ///
+    /// assert_cli::Assert::command(&["cat", "non-existing-file"])
+    ///     .fails_with(1)
+    ///     .and()
+    ///     .stderr().is("cat: non-existing-file: No such file or directory")
+    ///     .unwrap();
+    /// ```
+    pub fn stderr(self) -> OutputAssertionBuilder {
+        OutputAssertionBuilder {
+            assertion: self,
+            kind: OutputKind::StdErr,
+            stripped: false,
+        }
+    }
+
+    /// Run the command and check the assertions again, up to `retries` more times, until they
+    /// pass.
+    ///
+    /// Waits `backoff` before the first retry, doubling the wait for each further one.  The
+    /// failures of earlier attempts are kept in the final error, see
+    /// [`AssertionError::previous_attempts`](struct.AssertionError.html#method.previous_attempts).
+    /// Input from [`stdin_reader`](#method.stdin_reader) is read into memory first, so each
+    /// attempt gets it.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// extern crate assert_cli;
+    /// use std::time::Duration;
+    ///
+    /// let err = assert_cli::Assert::command(&["false"])
+    ///     .retry(2, Duration::from_millis(10))
+    ///     .execute()
+    ///     .unwrap_err();
+    /// assert_eq!(err.previous_attempts().len(), 2);
+    /// ```
+    pub fn retry(mut self, retries: u32, backoff: Duration) -> Self {
+        self.retries = retries;
+        self.backoff = backoff;
+        self
+    }
+
+    /// Run the command `runs` times, failing if any assertion holds in some runs but not in
+    /// others.
+    ///
+    /// The failure lists each assertion that varied, with the runs it held and failed in.  When
+    /// all runs agree, the result is that of the last run.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// extern crate assert_cli;
+    ///
+    /// assert_cli::Assert::command(&["echo", "42"])
+    ///     .check_flakiness(3)
+    ///     .stdout().is("42")
+    ///     .unwrap();
+    /// ```
+    pub fn check_flakiness(mut self, runs: usize) -> Self {
+        self.flaky_runs = Some(runs.max(1));
+        self
+    }
+
+    /// Run the command `runs` times, failing unless stdout, stderr and the exit code are the same
+    /// every time.
+    ///
+    /// Each run gets a fresh copy of the [`current_dir`](#method.current_dir), or an empty
+    /// directory without one, so runs can't see each other's files.  Can't be combined with
+    /// [`check_flakiness`](#method.check_flakiness).  Input from
+    /// [`stdin_reader`](#method.stdin_reader) is read into memory first, so each run gets it.  The
+    /// failure shows the differences to the first run.  When all runs agree, the assertions are
+    /// checked against the last one.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// extern crate assert_cli;
+    ///
+    /// assert_cli::Assert::command(&["echo", "42"])
+    ///     .deterministic(3)
+    ///     .stdout().is("42")
+    ///     .unwrap();
+    ///
+    /// let x = assert_cli::Assert::command(&["sh", "-c", "echo $$"])
+    ///     .deterministic(2)
+    ///     .execute();
+    /// assert!(x.is_err());
+    /// ```
+    pub fn deterministic(mut self, runs: usize) -> Self {
+        self.deterministic_runs = Some(runs.max(1));
+        self
+    }
+
+    /// Like [`deterministic`](#method.deterministic), also comparing the files in the copies of
+    /// the working directory after each run.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// extern crate assert_cli;
+    ///
+    /// assert_cli::Assert::command(&["sh", "-c", "wc -l lib.rs > lines"])
+    ///     .current_dir("src")
+    ///     .deterministic_with_files(2)
+    ///     .unwrap();
+    /// ```
+    pub fn deterministic_with_files(mut self, runs: usize) -> Self {
+        self.deterministic_files = true;
+        self.deterministic(runs)
+    }
+
+    /// Execute the command and check the assertions.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// extern crate assert_cli;
+    ///
+    /// let test = assert_cli::Assert::command(&["echo", "42"])
     ///     .stdout().contains("42")
     ///     .execute();
     /// assert!(test.is_ok());
     /// ```
-    pub fn execute(self) -> Result<(), AssertionError> {
-        let bin = &self.cmd[0];
-
-        let args: Vec<_> = self.cmd.iter().skip(1).collect();
-        let mut command = Command::new(bin);
-        let command = command
-            .stdin(Stdio::piped())
-            .stdout(Stdio::piped())
-            .stderr(Stdio::piped())
-            .env_clear()
-            .envs(self.env.clone().compile())
-            .args(&args);
-
-        let command = match self.current_dir {
-            Some(ref dir) => command.current_dir(dir),
-            None => command,
+    pub fn execute(mut self) -> Result<Outcome, AssertionError> {
+        self.prepare_execution()?;
+        let mut failures = vec![];
+        let mut backoff = self.backoff();
+        loop {
+            match self.attempt() {
+                Ok(outcome) => return Ok(outcome),
+                Err(err) => thread::sleep(self.retry_after(&mut failures, &mut backoff, err)?),
+            }
+        }
+    }
+
+    /// Check the settings, and get stdin ready for as many runs as needed.
+    pub(crate) fn prepare_execution(&mut self) -> Result<(), AssertionError> {
+        if let Some(err) = self.setup_error.take() {
+            return Err(AssertionError::new(self.cmd.clone()).chain(err));
+        }
+
+        // Before runs move to directories of their own.
+        if let Some(stdin) = self.stdin.relative_to(self.current_dir.as_deref()) {
+            self.stdin = stdin;
+        }
+
+        if let Some(ref code) = self.expect_exit_code {
+            let code = code
+                .resolve(&self.exit_code_names)
+                .chain_with(|| AssertionError::new(self.cmd.clone()))?;
+            self.expect_exit_code = Some(code);
+        }
+
+        if self.retries > 0 || self.flaky_runs.is_some() || self.deterministic_runs.is_some() {
+            self.stdin
+                .buffer()
+                .chain_with(|| AssertionError::new(self.cmd.clone()))?;
+        }
+
+        if self.flaky_runs.is_some() && self.deterministic_runs.is_some() {
+            let err = io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "`check_flakiness` and `deterministic` can't be combined",
+            );
+            return Err(AssertionError::new(self.cmd.clone()).chain(err));
+        }
+        Ok(())
+    }
+
+    /// Keep a failed attempt for the final error, returning how long to wait before retrying,
+    /// or the final error once out of retries.
+    pub(crate) fn retry_after(
+        &self,
+        failures: &mut Vec<AssertionError>,
+        backoff: &mut Duration,
+        err: AssertionError,
+    ) -> Result<Duration, AssertionError> {
+        if failures.len() < self.retries as usize {
+            failures.push(err);
+            let wait = *backoff;
+            *backoff *= 2;
+            Ok(wait)
+        } else {
+            Err(err.with_previous_attempts(mem::take(failures)))
+        }
+    }
+
+    /// Run the command as often as one attempt takes and check the assertions.
+    fn attempt(&mut self) -> Result<Outcome, AssertionError> {
+        let mut attempt = Attempt::new(self);
+        loop {
+            attempt.before_run(self)?;
+            let run = self.run();
+            if let Some(outcome) = attempt.after_run(self, run)? {
+                return Ok(outcome);
+            }
+        }
+    }
+
+    /// Whether each assertion `verify` checks holds, for comparing runs.
+    fn checks(&self, run: &Run) -> Vec<(String, bool)> {
+        let captured = &run.captured;
+        let output = &captured.output;
+        let mut checks = vec![];
+        if let Some(max) = self.output_limit.max_bytes {
+            for &kind in &[OutputKind::StdOut, OutputKind::StdErr] {
+                let label = format!("{} within {} bytes", kind, max);
+                checks.push((label, captured.len(kind) <= max));
+            }
+        }
+        if let Some(limit) = self.expect_max_duration {
+            let label = format!("completes within {:?}", limit);
+            checks.push((label, !captured.timed_out && run.elapsed <= limit));
+        }
+        if let Some(limit) = self.expect_min_duration {
+            checks.push((format!("takes at least {:?}", limit), run.elapsed >= limit));
+        }
+        if let Some(max) = self.expect_max_rss {
+            let label = format!("max RSS below {} bytes", max);
+            checks.push((label, within(Resource::MaxRss(max), captured.usage)));
+        }
+        if let Some(max) = self.expect_max_cpu_time {
+            let label = format!("CPU time below {:?}", max);
+            checks.push((label, within(Resource::CpuTime(max), captured.usage)));
+        }
+        for &(limit, value) in &self.rlimits {
+            let label = format!("not killed by {}={}", limit.name(), value);
+            checks.push((
+                label,
+                rlimit::hit(&[(limit, value)], output.status).is_none(),
+            ));
+        }
+        if !captured.stopped {
+            if let Some(expect_success) = self.expect_success {
+                let label = if expect_success { "succeeds" } else { "fails" };
+                checks.push((label.to_owned(), output.status.success() == expect_success));
+            }
+            if let Some(ref code) = self.expect_exit_code {
+                let label = format!("exits with {}", code.describe(&self.exit_code_names));
+                checks.push((label, code.accepts(output.status.code())));
+            }
+        }
+        let renderer = Renderer::new(self.diff_style, ColorChoice::Never);
+        checks.extend(
+            self.expect_output
+                .iter()
+                .map(|a| (a.to_string(), a.holds(output, &renderer))),
+        );
+        // Updating overwrites the expected directories, leaving nothing to compare.
+        for dir in self.expect_dirs.iter().filter(|dir| !dir.update) {
+            let label = format!(
+                "directory `{}` matches `{}`",
+                dir.actual.display(),
+                dir.expected.display()
+            );
+            let matches = dir.verify(run.current_dir.as_deref(), &renderer).is_ok();
+            checks.push((label, matches));
+        }
+        checks
+    }
+
+    /// Check the assertions against a run, adding its details to a failure.
+    fn finish(&self, mut run: Run) -> Result<Outcome, AssertionError> {
+        let elapsed = run.elapsed;
+        let usage = run.captured.usage;
+        let output = run.captured.output.clone();
+        let invocation = run.invocation.take();
+        let show_invocation = self.show_invocation;
+        self.verify(run).map_err(|err| {
+            let err = err.with_elapsed(elapsed);
+            match invocation {
+                Some(invocation) if show_invocation => err.with_invocation(invocation),
+                _ => err,
+            }
+        })?;
+        Ok(Outcome {
+            elapsed,
+            usage,
+            output,
+        })
+    }
+
+    /// Include the effective program, arguments, environment, working directory and stdin in
+    /// failure messages.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// extern crate assert_cli;
+    ///
+    /// let err = assert_cli::Assert::command(&["false"])
+    ///     .with_env(&[("DEBUG", "1")])
+    ///     .show_invocation()
+    ///     .execute()
+    ///     .unwrap_err();
+    /// assert!(err.to_string().contains("\n  DEBUG=1"));
+    /// ```
+    pub fn show_invocation(mut self) -> Self {
+        self.show_invocation = true;
+        self
+    }
+
+    /// Resolve how the command would be started, without starting it.
+    ///
+    /// With [`hermetic`](#method.hermetic), the sandbox is created and lives as long as the
+    /// returned `Invocation`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// extern crate assert_cli;
+    ///
+    /// let invocation = assert_cli::Assert::command(&["echo", "42"])
+    ///     .with_env(assert_cli::Environment::empty().insert("FOO", "BAR"))
+    ///     .stdin("hello")
+    ///     .dry_run()
+    ///     .unwrap();
+    /// assert_eq!(invocation.args(), ["42"]);
+    /// assert_eq!(invocation.env_var("FOO").unwrap(), "BAR");
+    /// assert_eq!(invocation.env().len(), 1);
+    /// assert_eq!(invocation.stdin_len(), Some(5));
+    /// ```
+    pub fn dry_run(&self) -> Result<Invocation, AssertionError> {
+        self.invocation()
+    }
+
+    fn invocation(&self) -> Result<Invocation, AssertionError> {
+        let sandbox = if self.hermetic {
+            let sandbox = Sandbox::create(&self.seeds)
+                .chain_with(|| AssertionError::new(self.cmd.clone()))?;
+            Some(sandbox)
+        } else {
+            None
         };
+        let env = self.env.clone().compile();
+        let mut env = match sandbox {
+            Some(ref sandbox) => sandbox.isolate(env),
+            None => env,
+        };
+        if let Some(tty) = self.tty {
+            env.retain(|(k, _)| k != "COLUMNS" && k != "LINES");
+            env.extend(tty.env());
+        }
+        let resolved = self.stdin.relative_to(self.current_dir.as_deref());
+        let stdin = resolved.as_ref().unwrap_or(&self.stdin);
+        Ok(Invocation::new(
+            &self.cmd,
+            env,
+            self.current_dir.clone(),
+            stdin.describe(),
+            stdin.len(),
+            sandbox,
+        ))
+    }
 
-        let mut spawned = command
-            .spawn()
+    /// Set up the command for a run, without spawning it.
+    pub(crate) fn prepare(&mut self) -> Result<Prepared, AssertionError> {
+        let invocation = self.invocation()?;
+        let stdin = self.stdin.replay();
+        let stdin_stdio = stdin
+            .stdio()
             .chain_with(|| AssertionError::new(self.cmd.clone()))?;
 
-        if let Some(ref contents) = self.stdin_contents {
-            spawned
-                .stdin
-                .as_mut()
-                .expect("Couldn't get mut ref to command stdin")
-                .write_all(contents)
-                .chain_with(|| AssertionError::new(self.cmd.clone()))?;
+        let mut ptys = vec![];
+        if let Some(tty) = self.tty {
+            for &(kind, attached) in &[
+                (OutputKind::StdOut, tty.stdout),
+                (OutputKind::StdErr, tty.stderr),
+            ] {
+                if let Some(pty) = tty
+                    .open(attached)
+                    .chain_with(|| AssertionError::new(self.cmd.clone()))?
+                {
+                    ptys.push((kind, pty));
+                }
+            }
         }
-        let output = spawned
-            .wait_with_output()
+        let stdio = |kind| match ptys.iter().find(|&&(k, _)| k == kind) {
+            Some((_, pty)) => pty.stdio(),
+            None => Ok(Stdio::piped()),
+        };
+        let stdout_stdio =
+            stdio(OutputKind::StdOut).chain_with(|| AssertionError::new(self.cmd.clone()))?;
+        let stderr_stdio =
+            stdio(OutputKind::StdErr).chain_with(|| AssertionError::new(self.cmd.clone()))?;
+
+        let mut command = invocation.command();
+        command
+            .stdin(stdin_stdio)
+            .stdout(stdout_stdio)
+            .stderr(stderr_stdio);
+        rlimit::apply(&mut command, &self.rlimits)
+            .map_err(|err| AssertionError::new(self.cmd.clone()).chain(err))?;
+
+        Ok(Prepared {
+            invocation,
+            command,
+            stdin,
+            ptys,
+            current_dir: self.current_dir.clone(),
+            limit: Limit {
+                timeout: self.expect_max_duration,
+                ..self.output_limit
+            },
+        })
+    }
+
+    /// The failure for a command that couldn't be spawned.
+    pub(crate) fn spawn_error(&self, err: io::Error, invocation: Invocation) -> AssertionError {
+        let err = AssertionError::new(self.cmd.clone()).chain(err);
+        if self.show_invocation {
+            err.with_invocation(invocation)
+        } else {
+            err
+        }
+    }
+
+    /// Spawn the command and wait for it, checking output as it arrives.
+    fn run(&mut self) -> Result<Run, AssertionError> {
+        let Prepared {
+            invocation,
+            mut command,
+            stdin,
+            ptys,
+            current_dir,
+            limit,
+        } = self.prepare()?;
+
+        let start = Instant::now();
+        let mut spawned = match command.spawn() {
+            Ok(spawned) => spawned,
+            Err(err) => return Err(self.spawn_error(err, invocation)),
+        };
+        // Close our copies of the terminals, so reading them ends when the command exits.
+        drop(command);
+        let streams = ptys
+            .into_iter()
+            .map(|(kind, pty)| (kind, Box::new(pty.into_reader()) as Box<dyn Read + Send>))
+            .collect();
+
+        let feeder = stdin.feed(spawned.stdin.take());
+        let mut watcher = Watcher::new(self);
+        let captured = capture::capture(&mut spawned, streams, limit, |kind, got, seen| {
+            watcher.watch(self, kind, got, seen)
+        })
+        .chain_with(|| AssertionError::new(self.cmd.clone()))?;
+        feeder
+            .join()
             .chain_with(|| AssertionError::new(self.cmd.clone()))?;
 
-        if let Some(expect_success) = self.expect_success {
-            let actual_success = output.status.success();
-            if expect_success != actual_success {
+        Ok(Run {
+            captured,
+            early_failure: watcher.early_failure,
+            elapsed: start.elapsed(),
+            current_dir,
+            invocation: Some(invocation),
+        })
+    }
+
+    /// Check the assertions against a finished command.
+    fn verify(&self, run: Run) -> Result<(), AssertionError> {
+        let Run {
+            captured,
+            early_failure,
+            elapsed,
+            current_dir,
+            ..
+        } = run;
+
+        if let Some(err) = early_failure {
+            let output = captured.output;
+            return Err(AssertionError::new(self.cmd.clone())
+                .chain(TerminatedError::new(output.stdout, output.stderr).chain(err)));
+        }
+
+        if let Some(max) = self.output_limit.max_bytes {
+            for &kind in &[OutputKind::StdOut, OutputKind::StdErr] {
+                let len = captured.len(kind);
+                if len > max {
+                    let got = kind.select(&captured.output);
+                    let err = OutputTooLargeError::new(max, len, !captured.overflowed, got);
+                    return Err(AssertionError::new(self.cmd.clone())
+                        .chain(OutputError::new(kind).chain(err)));
+                }
+            }
+        }
+        let output = captured.output;
+
+        if let Some(limit) = self.expect_max_duration {
+            if captured.timed_out || elapsed > limit {
+                return Err(
+                    AssertionError::new(self.cmd.clone()).chain(TimingError::new(
+                        Bound::AtMost(limit),
+                        elapsed,
+                        captured.timed_out,
+                        output.stdout,
+                        output.stderr,
+                    )),
+                );
+            }
+        }
+
+        if let Some(limit) = self.expect_min_duration {
+            if elapsed < limit {
                 return Err(
-                    AssertionError::new(self.cmd.clone()).chain(StatusError::new(
-                        actual_success,
-                        output.stdout.clone(),
-                        output.stderr.clone(),
+                    AssertionError::new(self.cmd.clone()).chain(TimingError::new(
+                        Bound::AtLeast(limit),
+                        elapsed,
+                        false,
+                        output.stdout,
+                        output.stderr,
                     )),
-                )?;
+                );
+            }
+        }
+
+        let usage = captured.usage;
+        let limits = [
+            self.expect_max_rss.map(Resource::MaxRss),
+            self.expect_max_cpu_time.map(Resource::CpuTime),
+        ];
+        for &limit in limits.iter().flatten() {
+            if !within(limit, usage) {
+                return Err(
+                    AssertionError::new(self.cmd.clone()).chain(ResourceError::new(limit, usage))
+                );
+            }
+        }
+
+        // The status of a command we stopped ourselves says nothing about the command.
+        let (expect_success, expect_exit_code) = if captured.stopped {
+            (None, None)
+        } else {
+            (self.expect_success, self.expect_exit_code.clone())
+        };
+
+        if let Some(expect_success) = expect_success {
+            let actual_success = output.status.success();
+            if expect_success != actual_success {
+                let mut err =
+                    StatusError::new(actual_success, output.stdout.clone(), output.stderr.clone());
+                if let Some(hit) = rlimit::hit(&self.rlimits, output.status) {
+                    err = err.chain(hit);
+                }
+                return Err(AssertionError::new(self.cmd.clone()).chain(err))?;
             }
         }
 
-        if self.expect_exit_code.is_some() && self.expect_exit_code != output.status.code() {
-            return Err(
-                AssertionError::new(self.cmd.clone()).chain(ExitCodeError::new(
-                    self.expect_exit_code,
+        if let Some(expect_exit_code) = expect_exit_code {
+            if !expect_exit_code.accepts(output.status.code()) {
+                let mut err = ExitCodeError::new(
+                    expect_exit_code,
+                    self.exit_code_names.clone(),
                     output.status.code(),
                     output.stdout.clone(),
                     output.stderr.clone(),
-                )),
-            );
+                );
+                if let Some(hit) = rlimit::hit(&self.rlimits, output.status) {
+                    err = err.chain(hit);
+                }
+                return Err(AssertionError::new(self.cmd.clone()).chain(err));
+            }
         }
 
+        let renderer = Renderer::new(self.diff_style, self.color);
         self.expect_output
             .iter()
             .map(|a| {
-                a.verify(&output)
+                a.verify(&output, &renderer)
                     .chain_with(|| AssertionError::new(self.cmd.clone()))
             })
             .collect::<Result<Vec<()>, AssertionError>>()?;
 
+        for dir in &self.expect_dirs {
+            dir.verify(current_dir.as_deref(), &renderer)
+                .map_err(|err| AssertionError::new(self.cmd.clone()).chain(err))?;
+        }
+
         Ok(())
     }
 
@@ -442,24 +1663,475 @@ impl Assert {
         }
     }
 
-    fn format_causes(mut causes: failure::Causes) -> String {
+    /// Run the command with `--help`, check the assertions, and parse what it printed.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// extern crate assert_cli;
+    ///
+    /// # if cfg!(unix) {
+    /// let help = assert_cli::Assert::command(&["sh", "-c", "echo 'Options:'; echo '  -v, --verbose  Print more'", "mytool"])
+    ///     .help()
+    ///     .unwrap();
+    /// assert_eq!(help.flag("--verbose").unwrap().description(), "Print more");
+    /// # }
+    /// ```
+    pub fn help(self) -> Result<Help, AssertionError> {
+        let outcome = self.with_args(&["--help"]).execute()?;
+        Ok(Help::parse(&String::from_utf8_lossy(outcome.stdout())))
+    }
+
+    /// Like [`help`](#method.help), then run `--help` of every subcommand listed, and of
+    /// theirs in turn, checking the assertions for each.
+    ///
+    /// The `help` subcommand is skipped.  A subcommand printing the same help as its parent, or
+    /// any command above, fails, since the command likely ignored it, as do subcommands nested
+    /// more than 8 levels deep, which likely loop back.  The help of each subcommand is
+    /// available through [`Subcommand::help`](struct.Subcommand.html#method.help).
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// extern crate assert_cli;
+    ///
+    /// # if cfg!(unix) {
+    /// let tool = r#"
+    ///     case "$1" in
+    ///         --help) printf 'Usage: tool <COMMAND>\n\nCommands:\n  build  Build it\n  help   Print help\n' ;;
+    ///         build) echo 'Usage: tool build [--release]' ;;
+    ///         *) exit 2 ;;
+    ///     esac
+    /// "#;
+    /// let help = assert_cli::Assert::command(&["sh", "-c", tool, "tool"])
+    ///     .stdout().contains("Usage")
+    ///     .help_recursive()
+    ///     .unwrap();
+    /// let build = help.subcommand("build").unwrap().help().unwrap();
+    /// assert_eq!(build.usage(), Some("tool build [--release]"));
+    /// # }
+    /// ```
+    pub fn help_recursive(mut self) -> Result<Help, AssertionError> {
+        self.prepare_execution()?;
+        self.stdin
+            .buffer()
+            .chain_with(|| AssertionError::new(self.cmd.clone()))?;
+        let base = self.cmd.clone();
+        self.subcommand_help(&base, &mut vec![], &mut vec![])
+    }
+
+    /// Parse the help of the subcommand at `path`, and of its subcommands.
+    ///
+    /// `ancestors` holds the help printed for each prefix of `path`.
+    fn subcommand_help(
+        &mut self,
+        base: &[OsString],
+        path: &mut Vec<String>,
+        ancestors: &mut Vec<String>,
+    ) -> Result<Help, AssertionError> {
+        self.cmd = base.to_vec();
+        self.cmd.extend(path.iter().map(OsString::from));
+        self.cmd.push("--help".into());
+        if path.len() > MAX_HELP_DEPTH {
+            let msg = format!(
+                "Subcommands nest more than {} levels deep, so they likely loop back",
+                MAX_HELP_DEPTH
+            );
+            return Err(AssertionError::new(self.cmd.clone()).chain(failure::err_msg(msg)));
+        }
+        let outcome = self.attempt()?;
+        let text = String::from_utf8_lossy(outcome.stdout()).into_owned();
+        if let Some(level) = ancestors.iter().position(|help| *help == text) {
+            let msg = if level + 1 == ancestors.len() {
+                "Printed the same help as its parent command, so it likely ignores the subcommand"
+                    .to_owned()
+            } else {
+                let ancestor: Vec<_> = base
+                    .iter()
+                    .map(|arg| arg.to_string_lossy())
+                    .chain(path[..level].iter().map(|name| name.as_str().into()))
+                    .collect();
+                format!(
+                    "Printed the same help as `{}`, so subcommands likely loop back",
+                    ancestor.join(" ")
+                )
+            };
+            return Err(AssertionError::new(self.cmd.clone()).chain(failure::err_msg(msg)));
+        }
+
+        let mut help = Help::parse(&text);
+        ancestors.push(text);
+        for subcommand in &mut help.subcommands {
+            if subcommand.name() == "help" {
+                continue;
+            }
+            path.push(subcommand.name().to_owned());
+            let result = self.subcommand_help(base, path, ancestors);
+            path.pop();
+            subcommand.help = Some(Box::new(result?));
+        }
+        ancestors.pop();
+        Ok(help)
+    }
+
+    pub(crate) fn format_causes(mut causes: failure::Causes) -> String {
         let mut result = causes.next().expect("an error should exist").to_string();
         for cause in causes {
             result.push_str(&format!("\nwith: {}", cause));
         }
         result
     }
-}
+}
+
+/// A command ready to spawn, see `Assert::prepare`.
+pub(crate) struct Prepared {
+    pub invocation: Invocation,
+    pub command: Command,
+    pub stdin: Stdin,
+    /// Terminals the command writes to instead of pipes.
+    pub ptys: Vec<(OutputKind, Pty)>,
+    pub current_dir: Option<PathBuf>,
+    pub limit: Limit,
+}
+
+/// Checks output as it arrives, failing or stopping the command early.
+pub(crate) struct Watcher {
+    pub early_failure: Option<OutputError>,
+    pending_stops: usize,
+    stopped: Vec<bool>,
+}
+
+impl Watcher {
+    pub fn new(assert: &Assert) -> Self {
+        Self {
+            early_failure: None,
+            pending_stops: assert.expect_output.iter().filter(|a| a.stops()).count(),
+            stopped: vec![false; assert.expect_output.len()],
+        }
+    }
+
+    /// Check the output of `kind` that grew to `got`, `seen` bytes of it having been checked.
+    pub fn watch(&mut self, assert: &Assert, kind: OutputKind, got: &[u8], seen: usize) -> Watch {
+        for (i, a) in assert.expect_output.iter().enumerate() {
+            if a.kind() != kind {
+                continue;
+            }
+            if let Err(err) = a.verify_partial(got, seen) {
+                self.early_failure = Some(err);
+                return Watch::Stop;
+            }
+            if a.stops() && !self.stopped[i] && a.found_since(got, seen) {
+                self.stopped[i] = true;
+                self.pending_stops -= 1;
+                if self.pending_stops == 0 {
+                    return Watch::Stop;
+                }
+            }
+        }
+        Watch::Continue
+    }
+}
+
+/// A finished command, before its assertions are checked.
+pub(crate) struct Run {
+    pub captured: Captured,
+    pub early_failure: Option<OutputError>,
+    pub elapsed: Duration,
+    /// Where the command ran, for assertions on the files it wrote.
+    pub current_dir: Option<PathBuf>,
+    /// Kept until the assertions were checked, for the sandbox it may own.
+    pub invocation: Option<Invocation>,
+}
+
+/// The runs of one attempt at the assertions, and what is checked between them.
+///
+/// Drives both the blocking and the async execution.
+pub(crate) enum Attempt {
+    Once,
+    /// Runs whose assertions must all fare the same, see `Assert::check_flakiness`.
+    Repeatedly {
+        runs: usize,
+        checks: Vec<Vec<(String, bool)>>,
+        result: Option<Result<Outcome, AssertionError>>,
+    },
+    /// Runs in fresh directories that must all produce the same, see `Assert::deterministic`.
+    Deterministic {
+        runs: usize,
+        done: usize,
+        source: Option<PathBuf>,
+        dir: Option<TempDir>,
+        first: Option<Snapshot>,
+    },
+}
+
+impl Attempt {
+    pub fn new(assert: &Assert) -> Self {
+        match (assert.flaky_runs, assert.deterministic_runs) {
+            (Some(runs), _) => Attempt::Repeatedly {
+                runs,
+                checks: vec![],
+                result: None,
+            },
+            (None, Some(runs)) => Attempt::Deterministic {
+                runs,
+                done: 0,
+                source: assert.current_dir.clone(),
+                dir: None,
+                first: None,
+            },
+            (None, None) => Attempt::Once,
+        }
+    }
+
+    /// Get ready for the next run.
+    pub fn before_run(&mut self, assert: &mut Assert) -> Result<(), AssertionError> {
+        if let Attempt::Deterministic {
+            ref source,
+            ref mut dir,
+            ..
+        } = *self
+        {
+            let fresh = tempfile::Builder::new()
+                .prefix("assert_cli-")
+                .tempdir()
+                .and_then(|fresh| match *source {
+                    Some(ref source) => determinism::copy_dir(source, fresh.path()).map(|_| fresh),
+                    None => Ok(fresh),
+                })
+                .chain_with(|| AssertionError::new(assert.cmd.clone()))?;
+            assert.current_dir = Some(fresh.path().to_owned());
+            *dir = Some(fresh);
+        }
+        Ok(())
+    }
+
+    /// Take in a run, returning the outcome once no more runs are needed.
+    pub fn after_run(
+        &mut self,
+        assert: &mut Assert,
+        run: Result<Run, AssertionError>,
+    ) -> Result<Option<Outcome>, AssertionError> {
+        match *self {
+            Attempt::Once => assert.finish(run?).map(Some),
+            Attempt::Repeatedly {
+                runs,
+                ref mut checks,
+                ref mut result,
+            } => {
+                let run = run?;
+                checks.push(assert.checks(&run));
+                *result = Some(assert.finish(run));
+                if checks.len() < runs {
+                    return Ok(None);
+                }
+                let varied = variations(checks);
+                if !varied.is_empty() {
+                    return Err(AssertionError::new(assert.cmd.clone())
+                        .chain(FlakyError::new(runs, varied)));
+                }
+                result.take().expect("at least one run").map(Some)
+            }
+            Attempt::Deterministic {
+                runs,
+                ref mut done,
+                ref source,
+                ref dir,
+                ref mut first,
+            } => {
+                assert.current_dir = source.clone();
+                let run = run?;
+                *done += 1;
+                let files = match *dir {
+                    Some(ref dir) if assert.deterministic_files => Some(
+                        determinism::read_tree(dir.path())
+                            .chain_with(|| AssertionError::new(assert.cmd.clone()))?,
+                    ),
+                    _ => None,
+                };
+                let snapshot = Snapshot::new(&run.captured.output, files);
+                match *first {
+                    None => *first = Some(snapshot),
+                    Some(ref first) => {
+                        let renderer = Renderer::new(assert.diff_style, assert.color);
+                        let differences = first.differences(&snapshot, &renderer);
+                        if !differences.is_empty() {
+                            return Err(AssertionError::new(assert.cmd.clone())
+                                .chain(DeterminismError::new(*done, differences)));
+                        }
+                    }
+                }
+                if *done < runs {
+                    Ok(None)
+                } else {
+                    assert.finish(run).map(Some)
+                }
+            }
+        }
+    }
+}
+
+/// Whether `usage` stays below `limit`, failing when it wasn't collected.
+fn within(limit: Resource, usage: Option<ResourceUsage>) -> bool {
+    match (limit, usage) {
+        (Resource::MaxRss(max), Some(usage)) => usage.max_rss() < max,
+        (Resource::CpuTime(max), Some(usage)) => usage.cpu_time() < max,
+        (_, None) => false,
+    }
+}
+
+/// The assertions that held in some runs but not in others.
+fn variations(checks: &[Vec<(String, bool)>]) -> Vec<Variation> {
+    let runs = checks.len();
+    checks[0]
+        .iter()
+        .enumerate()
+        .filter_map(|(i, (assertion, _))| {
+            let (held, failed): (Vec<_>, Vec<_>) =
+                (1..=runs).partition(|&run| checks[run - 1][i].1);
+            if held.is_empty() || failed.is_empty() {
+                return None;
+            }
+            Some(Variation {
+                assertion: assertion.clone(),
+                held,
+                failed,
+            })
+        })
+        .collect()
+}
+
+/// Details about a command whose assertions passed.
+#[derive(Debug, Clone)]
+pub struct Outcome {
+    elapsed: Duration,
+    usage: Option<ResourceUsage>,
+    output: process::Output,
+}
+
+impl Outcome {
+    /// Wall-clock time between spawning the command and its exit.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// extern crate assert_cli;
+    ///
+    /// let outcome = assert_cli::Assert::command(&["echo", "42"])
+    ///     .execute()
+    ///     .unwrap();
+    /// assert!(outcome.elapsed().as_secs() < 10);
+    /// ```
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+
+    /// Resources used by the command, only available on Linux.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// extern crate assert_cli;
+    ///
+    /// let outcome = assert_cli::Assert::command(&["echo", "42"])
+    ///     .execute()
+    ///     .unwrap();
+    /// if let Some(usage) = outcome.resource_usage() {
+    ///     assert!(usage.max_rss() > 0);
+    /// }
+    /// ```
+    pub fn resource_usage(&self) -> Option<&ResourceUsage> {
+        self.usage.as_ref()
+    }
+
+    /// The command's exit code, `None` if it was killed by a signal.
+    pub fn code(&self) -> Option<i32> {
+        self.output.status.code()
+    }
+
+    /// What the command wrote to stdout, up to the output limit.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// extern crate assert_cli;
+    ///
+    /// let outcome = assert_cli::Assert::command(&["echo", "42"])
+    ///     .execute()
+    ///     .unwrap();
+    /// assert_eq!(outcome.stdout(), b"42\n");
+    /// ```
+    pub fn stdout(&self) -> &[u8] {
+        &self.output.stdout
+    }
+
+    /// What the command wrote to stderr, up to the output limit.
+    pub fn stderr(&self) -> &[u8] {
+        &self.output.stderr
+    }
+}
+
+/// Assertions for command output.
+#[derive(Debug)]
+#[must_use]
+pub struct OutputAssertionBuilder {
+    assertion: Assert,
+    kind: OutputKind,
+    stripped: bool,
+}
+
+impl OutputAssertionBuilder {
+    /// Check the following assertion against the output without ANSI escape sequences.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// extern crate assert_cli;
+    ///
+    /// assert_cli::Assert::command(&["printf", "\\033[31merror\\033[0m: bad input"])
+    ///     .stdout().stripped().is("error: bad input")
+    ///     .unwrap();
+    /// ```
+    pub fn stripped(mut self) -> Self {
+        self.stripped = true;
+        self
+    }
+
+    /// Expect the command's output to not contain ANSI escape sequences.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// extern crate assert_cli;
+    ///
+    /// assert_cli::Assert::command(&["echo", "plain"])
+    ///     .stdout().has_no_ansi()
+    ///     .unwrap();
+    /// ```
+    pub fn has_no_ansi(self) -> Assert {
+        let pred = OutputPredicate::new(self.kind, Output::has_no_ansi());
+        self.push(pred)
+    }
 
-/// Assertions for command output.
-#[derive(Debug)]
-#[must_use]
-pub struct OutputAssertionBuilder {
-    assertion: Assert,
-    kind: OutputKind,
-}
+    /// Expect the command's output to contain `text` in `style`.
+    ///
+    /// `text` is looked for in the output without ANSI escape sequences, and each of its
+    /// characters needs to have at least the attributes `style` sets.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// extern crate assert_cli;
+    /// use assert_cli::{Assert, Style};
+    ///
+    /// Assert::command(&["printf", "\\033[1;31merror\\033[0m: bad input"])
+    ///     .stdout().styled_contains("error", Style::red())
+    ///     .unwrap();
+    /// ```
+    pub fn styled_contains<S: Into<String>>(self, text: S, style: Style) -> Assert {
+        let pred = OutputPredicate::new(self.kind, Output::styled_contains(text, style));
+        self.push(pred)
+    }
 
-impl OutputAssertionBuilder {
     /// Expect the command's output to **contain** `output`.
     ///
     /// # Examples
@@ -471,10 +2143,9 @@ impl OutputAssertionBuilder {
     ///     .stdout().contains("42")
     ///     .unwrap();
     /// ```
-    pub fn contains<O: Into<Content>>(mut self, output: O) -> Assert {
+    pub fn contains<O: Into<Content>>(self, output: O) -> Assert {
         let pred = OutputPredicate::new(self.kind, Output::contains(output));
-        self.assertion.expect_output.push(pred);
-        self.assertion
+        self.push(pred)
     }
 
     /// Expect the command to output **exactly** this `output`.
@@ -488,14 +2159,16 @@ impl OutputAssertionBuilder {
     ///     .stdout().is("42")
     ///     .unwrap();
     /// ```
-    pub fn is<O: Into<Content>>(mut self, output: O) -> Assert {
+    pub fn is<O: Into<Content>>(self, output: O) -> Assert {
         let pred = OutputPredicate::new(self.kind, Output::is(output));
-        self.assertion.expect_output.push(pred);
-        self.assertion
+        self.push(pred)
     }
 
     /// Expect the command's output to not **contain** `output`.
     ///
+    /// The output is checked while the command is running, and the command is terminated as soon
+    /// as `output` shows up.
+    ///
     /// # Examples
     ///
     /// ```rust
@@ -505,10 +2178,9 @@ impl OutputAssertionBuilder {
     ///     .stdout().doesnt_contain("73")
     ///     .unwrap();
     /// ```
-    pub fn doesnt_contain<O: Into<Content>>(mut self, output: O) -> Assert {
+    pub fn doesnt_contain<O: Into<Content>>(self, output: O) -> Assert {
         let pred = OutputPredicate::new(self.kind, Output::doesnt_contain(output));
-        self.assertion.expect_output.push(pred);
-        self.assertion
+        self.push(pred)
     }
 
     /// Expect the command to output to not be **exactly** this `output`.
@@ -522,10 +2194,61 @@ impl OutputAssertionBuilder {
     ///     .stdout().isnt("73")
     ///     .unwrap();
     /// ```
-    pub fn isnt<O: Into<Content>>(mut self, output: O) -> Assert {
+    pub fn isnt<O: Into<Content>>(self, output: O) -> Assert {
         let pred = OutputPredicate::new(self.kind, Output::isnt(output));
-        self.assertion.expect_output.push(pred);
-        self.assertion
+        self.push(pred)
+    }
+
+    /// Expect the command's output to **contain** `output`, and terminate the command as soon
+    /// as it does.
+    ///
+    /// This is meant for commands that don't exit on their own, like a watch mode.  Once all of
+    /// these assertions are met, the command is killed and its exit status is not checked.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// extern crate assert_cli;
+    ///
+    /// assert_cli::Assert::command(&["sh", "-c", "echo ready; exec sleep 60"])
+    ///     .stdout().contains_then_stop("ready")
+    ///     .unwrap();
+    /// ```
+    pub fn contains_then_stop<O: Into<Content>>(self, output: O) -> Assert {
+        let pred = OutputPredicate::stopping(self.kind, Output::contains(output));
+        self.push(pred)
+    }
+
+    /// Expect the command's output to be at most `max` bytes long.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// extern crate assert_cli;
+    ///
+    /// assert_cli::Assert::command(&["echo", "42"])
+    ///     .stdout().len_at_most(3)
+    ///     .unwrap();
+    /// ```
+    pub fn len_at_most(self, max: usize) -> Assert {
+        let pred = OutputPredicate::new(self.kind, Output::len_at_most(max));
+        self.push(pred)
+    }
+
+    /// Expect the command to not output anything.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// extern crate assert_cli;
+    ///
+    /// assert_cli::Assert::command(&["echo", "42"])
+    ///     .stderr().is_empty()
+    ///     .unwrap();
+    /// ```
+    pub fn is_empty(self) -> Assert {
+        let pred = OutputPredicate::new(self.kind, Output::is_empty());
+        self.push(pred)
     }
 
     /// Expect the command output to satisfy the given predicate.
@@ -539,21 +2262,70 @@ impl OutputAssertionBuilder {
     ///     .stdout().satisfies(|x| x.len() == 2, "bad length")
     ///     .unwrap();
     /// ```
-    pub fn satisfies<F, M>(mut self, pred: F, msg: M) -> Assert
+    pub fn satisfies<F, M>(self, pred: F, msg: M) -> Assert
     where
         F: 'static + Fn(&str) -> bool,
         M: Into<String>,
     {
         let pred = OutputPredicate::new(self.kind, Output::satisfies(pred, msg));
+        self.push(pred)
+    }
+
+    fn push(mut self, pred: OutputPredicate) -> Assert {
+        let pred = if self.stripped { pred.stripped() } else { pred };
         self.assertion.expect_output.push(pred);
         self.assertion
     }
 }
 
+/// Assertions for a directory the command writes.
+#[derive(Debug)]
+#[must_use]
+pub struct DirAssertionBuilder {
+    assertion: Assert,
+    actual: PathBuf,
+    permissions: bool,
+    symlinks: bool,
+}
+
+impl DirAssertionBuilder {
+    /// Also compare the permissions of files and directories.
+    pub fn with_permissions(mut self) -> Self {
+        self.permissions = true;
+        self
+    }
+
+    /// Compare symbolic links by their target, instead of following them.
+    pub fn with_symlinks(mut self) -> Self {
+        self.symlinks = true;
+        self
+    }
+
+    /// Expect the directory to have the same files and contents as `expected`.
+    ///
+    /// Each missing, unexpected or differing file is reported, with a diff of its contents.
+    ///
+    /// When the `ASSERT_CLI_UPDATE` environment variable is set, read once here, `expected` is
+    /// overwritten with the directory instead.
+    pub fn matches<P: Into<PathBuf>>(mut self, expected: P) -> Assert {
+        self.assertion.expect_dirs.push(GoldenDir {
+            actual: self.actual,
+            expected: expected.into(),
+            permissions: self.permissions,
+            symlinks: self.symlinks,
+            update: golden::update_requested(),
+        });
+        self.assertion
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
+    use std::env;
     use std::ffi::OsString;
+    use std::fs;
+    use std::process;
 
     fn command() -> Assert {
         Assert::command(&["printenv"])
@@ -753,6 +2525,542 @@ mod test {
             .unwrap();
     }
 
+    #[test]
+    fn stdin_larger_than_pipe_buffer() {
+        Assert::command(&["cat"])
+            .stdin_with(|stdin| {
+                for _ in 0..64 {
+                    stdin.write_all(&[b'x'; 64 * 1024])?;
+                }
+                Ok(())
+            })
+            .stdout()
+            .satisfies(|x| x.len() == 64 * 64 * 1024, "bad length")
+            .unwrap();
+    }
+
+    #[test]
+    fn stdin_ignored_by_command() {
+        Assert::command(&["true"])
+            .stdin(vec![b'x'; 1024 * 1024])
+            .unwrap();
+    }
+
+    #[test]
+    fn doesnt_contain_fails_early() {
+        let start = ::std::time::Instant::now();
+        let err = Assert::command(&["sh", "-c", "echo FATAL; exec sleep 60"])
+            .stdout()
+            .doesnt_contain("FATAL")
+            .execute()
+            .unwrap_err();
+        assert!(start.elapsed() < ::std::time::Duration::from_secs(30));
+
+        let message = Assert::format_causes(err.causes());
+        assert!(message.contains("Terminated the command early"));
+        assert!(message.contains("stdout so far=```FATAL"));
+    }
+
+    #[test]
+    fn contains_then_stop_needs_output() {
+        assert!(Assert::command(&["echo", "starting"])
+            .stdout()
+            .contains_then_stop("ready")
+            .execute()
+            .is_err());
+    }
+
+    #[test]
+    fn output_limit_truncates_capture() {
+        let err = Assert::command(&["seq", "100000"])
+            .max_output_bytes(100)
+            .execute()
+            .unwrap_err();
+        let message = Assert::format_causes(err.causes());
+        assert!(message.contains("Unexpected stdout"));
+        assert!(message.contains("Expected at most 100 bytes, got 588895 bytes."));
+    }
+
+    #[test]
+    fn output_limit_kills_command() {
+        let err = Assert::command(&["yes"])
+            .max_output_bytes(100)
+            .kill_on_output_limit()
+            .execute()
+            .unwrap_err();
+        let message = Assert::format_causes(err.causes());
+        assert!(message.contains("Expected at most 100 bytes, got at least"));
+    }
+
+    #[test]
+    fn output_within_limit() {
+        Assert::command(&["echo", "42"])
+            .max_output_bytes(3)
+            .stdout()
+            .is("42")
+            .unwrap();
+    }
+
+    #[test]
+    fn is_empty_fails_on_output() {
+        assert!(Assert::command(&["echo", "42"])
+            .stdout()
+            .is_empty()
+            .execute()
+            .is_err());
+    }
+
+    #[test]
+    fn completes_within_terminates_command() {
+        let err = Assert::command(&["sleep", "60"])
+            .completes_within(Duration::from_millis(100))
+            .execute()
+            .unwrap_err();
+        assert!(err.elapsed().unwrap() < Duration::from_secs(30));
+        let message = Assert::format_causes(err.causes());
+        assert!(message.contains("Expected to complete within 100ms, was terminated after"));
+    }
+
+    #[test]
+    fn takes_at_least_fails_fast_command() {
+        let err = Assert::command(&["true"])
+            .takes_at_least(Duration::from_secs(10))
+            .execute()
+            .unwrap_err();
+        let message = Assert::format_causes(err.causes());
+        assert!(message.starts_with("Assertion failed for `true` after "));
+        assert!(message.contains("Expected to take at least 10s, took"));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn max_rss_below_fails_on_large_command() {
+        let err = Assert::command(&["echo", "42"])
+            .max_rss_below(1)
+            .execute()
+            .unwrap_err();
+        let message = Assert::format_causes(err.causes());
+        assert!(message.contains("Expected peak RSS below 1 bytes, got "));
+        assert!(message.contains("usage: max_rss="));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn resource_usage_is_collected() {
+        let outcome = Assert::command(&["echo", "42"]).execute().unwrap();
+        let usage = outcome.resource_usage().unwrap();
+        assert!(usage.max_rss() > 0);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn rlimit_explains_killed_command() {
+        let path = env::temp_dir().join(format!("assert_cli-rlimit-{}", process::id()));
+        let err = Assert::command(&["sh", "-c", "exec head -c 4096 /dev/zero > \"$0\""])
+            .with_args(&[&path])
+            .rlimit(RLimit::FileSize, 1024)
+            .execute()
+            .unwrap_err();
+        let _ = fs::remove_file(&path);
+        let message = Assert::format_causes(err.causes());
+        assert!(message
+            .contains("Command was killed by SIGXFSZ, likely from hitting RLIMIT_FSIZE=1024"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn rlimit_above_hard_limit_fails() {
+        let err = Assert::command(&["true"])
+            .limit_open_files(u64::MAX - 1)
+            .execute()
+            .unwrap_err();
+        let message = Assert::format_causes(err.causes());
+        assert!(message.contains("Can't set RLIMIT_NOFILE="), "{}", message);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn tty_has_size_and_keeps_newlines() {
+        Assert::command(&[
+            "sh",
+            "-c",
+            "stty size <&1; echo $LINES $COLUMNS; echo oops >&2",
+        ])
+        .with_env(&[("COLUMNS", "10")])
+        .hermetic()
+        .tty(30, 120)
+        .stdout()
+        .is("30 120\n30 120\n")
+        .stderr()
+        .is("oops\n")
+        .unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn tty_output_is_watched() {
+        Assert::command(&["sh", "-c", "echo ready; exec sleep 10"])
+            .tty_stdout(24, 80)
+            .stdout()
+            .contains_then_stop("ready")
+            .completes_within(Duration::from_secs(5))
+            .unwrap();
+    }
+
+    /// A clap-style tool with `remote add` and `remote remove`, whose `remove` help fails.
+    const TOOL: &str = r#"
+        case "$*" in
+            --help) printf 'Usage: tool <COMMAND>\n\nCommands:\n  remote  Manage remotes\n  help    Print help\n' ;;
+            "remote --help") printf 'Usage: tool remote <COMMAND>\n\nCommands:\n  add, a  Add one\n  remove  Remove one\n' ;;
+            "remote add --help") echo 'Usage: tool remote add <NAME>' ;;
+            *) echo "unexpected: $*" >&2; exit 2 ;;
+        esac
+    "#;
+
+    #[test]
+    fn help_recursive_reports_failing_subcommand() {
+        let err = Assert::command(&["sh", "-c", TOOL, "tool"])
+            .help_recursive()
+            .unwrap_err();
+        let message = Assert::format_causes(err.causes());
+        assert!(
+            message.contains("`sh -c") && message.contains("tool remote remove --help`"),
+            "{}",
+            message
+        );
+        assert!(
+            message.contains("unexpected: remote remove --help"),
+            "{}",
+            message
+        );
+    }
+
+    #[test]
+    fn help_recursive_rejects_ignored_subcommands() {
+        let err = Assert::command(&["sh", "-c", "printf 'Commands:\\n  run  Run it\\n'", "tool"])
+            .help_recursive()
+            .unwrap_err();
+        let message = Assert::format_causes(err.causes());
+        assert!(message.contains("tool run --help`"), "{}", message);
+        assert!(
+            message.contains("Printed the same help as its parent command"),
+            "{}",
+            message
+        );
+    }
+
+    #[test]
+    fn help_recursive_rejects_loops() {
+        let tool = r#"
+            case "$*" in
+                --help|"a b --help") printf 'Usage: tool\n\nCommands:\n  a  Go deeper\n' ;;
+                *) printf 'Usage: tool a\n\nCommands:\n  b  Go back\n' ;;
+            esac
+        "#;
+        let err = Assert::command(&["sh", "-c", tool, "tool"])
+            .help_recursive()
+            .unwrap_err();
+        let message = Assert::format_causes(err.causes());
+        assert!(message.contains("tool a b --help`"), "{}", message);
+        assert!(
+            message.contains("Printed the same help as `sh -c"),
+            "{}",
+            message
+        );
+
+        let deeper = r#"printf 'Usage: tool %s\n\nCommands:\n  c  Go deeper\n' "$*""#;
+        let err = Assert::command(&["sh", "-c", deeper, "tool"])
+            .help_recursive()
+            .unwrap_err();
+        let message = Assert::format_causes(err.causes());
+        assert!(
+            message.contains("Subcommands nest more than 8 levels deep"),
+            "{}",
+            message
+        );
+    }
+
+    #[test]
+    fn help_recursive_collects_nested_help() {
+        let tool = TOOL
+            .replace("remove  Remove one", "rm  Remove one")
+            .replace(
+                "\"remote add --help\")",
+                "\"remote add --help\" | \"remote rm --help\")",
+            );
+        let help = Assert::command(&["sh", "-c", &tool, "tool"])
+            .help_recursive()
+            .unwrap();
+        let remote = help.subcommand("remote").unwrap().help().unwrap();
+        assert_eq!(remote.subcommand("a").unwrap().name(), "add");
+        let add = remote.subcommand("add").unwrap().help().unwrap();
+        assert_eq!(add.usage(), Some("tool remote add <NAME>"));
+        assert!(help.subcommand("help").unwrap().help().is_none());
+    }
+
+    #[test]
+    fn hermetic_isolates_environment() {
+        Assert::command(&[
+            "sh",
+            "-c",
+            "echo $HOME; echo $XDG_CONFIG_HOME; echo $LC_ALL",
+        ])
+        .with_env(&[("HOME", "/root"), ("LC_TIME", "de_DE")])
+        .hermetic()
+        .stdout()
+        .doesnt_contain("/root\n")
+        .stdout()
+        .contains("/home/.config\nC\n")
+        .unwrap();
+    }
+
+    #[test]
+    fn hermetic_rejects_escaping_seed() {
+        let err = Assert::command(&["true"])
+            .home_file("../outside", "")
+            .execute()
+            .unwrap_err();
+        let message = Assert::format_causes(err.causes());
+        assert!(message.contains("must be relative"), "{}", message);
+    }
+
+    #[test]
+    fn dry_run_resolves_hermetic_env() {
+        let invocation = Assert::command(&["true"])
+            .with_env(&[("FOO", "BAR")])
+            .config_file("tool/config.toml", "x = 1")
+            .dry_run()
+            .unwrap();
+        assert!(invocation.is_hermetic());
+        assert_eq!(invocation.env_var("TZ").unwrap(), "UTC");
+        assert_eq!(invocation.env_var("FOO").unwrap(), "BAR");
+        let config = PathBuf::from(invocation.env_var("XDG_CONFIG_HOME").unwrap());
+        assert!(config.join("tool/config.toml").is_file());
+    }
+
+    #[test]
+    fn show_invocation_on_spawn_failure() {
+        let err = Assert::command(&["assert_cli-does-not-exist", "--flag"])
+            .show_invocation()
+            .execute()
+            .unwrap_err();
+        let invocation = err.invocation().unwrap();
+        assert_eq!(invocation.args(), ["--flag"]);
+        assert!(err.to_string().contains("args: [\"--flag\"]"));
+    }
+
+    #[test]
+    fn retry_succeeds_once_ready() {
+        let marker = env::temp_dir().join(format!("assert_cli-retry-{}", process::id()));
+        let _ = fs::remove_file(&marker);
+        let script = "test -e \"$0\" || { touch \"$0\"; exit 1; }";
+        Assert::command(&["sh", "-c", script])
+            .with_args(&[&marker])
+            .retry(1, Duration::from_millis(1))
+            .unwrap();
+        let _ = fs::remove_file(&marker);
+    }
+
+    #[test]
+    fn retry_keeps_reader_input() {
+        let err = Assert::command(&["cat"])
+            .stdin_reader(io::Cursor::new("42"))
+            .retry(1, Duration::from_millis(1))
+            .stdout()
+            .is("43")
+            .execute()
+            .unwrap_err();
+        let previous = Assert::format_causes(err.previous_attempts()[0].causes());
+        assert!(previous.contains("42"), "{}", previous);
+        assert!(err.to_string().contains("attempt 1 failed: "));
+    }
+
+    #[test]
+    fn check_flakiness_reports_varying_assertion() {
+        let counter = env::temp_dir().join(format!("assert_cli-flaky-{}", process::id()));
+        let _ = fs::remove_file(&counter);
+        let script = "echo x >> \"$0\"; echo run $(wc -l < \"$0\")";
+        let err = Assert::command(&["sh", "-c", script])
+            .with_args(&[&counter])
+            .check_flakiness(3)
+            .stdout()
+            .contains("run 2")
+            .execute()
+            .unwrap_err();
+        let _ = fs::remove_file(&counter);
+        let message = Assert::format_causes(err.causes());
+        assert!(
+            message.contains("Outcomes differed across 3 runs:\n- stdout contains ```run 2```: held in runs 2, failed in runs 1, 3"),
+            "{}",
+            message
+        );
+    }
+
+    #[test]
+    fn check_flakiness_compares_timing() {
+        let counter = env::temp_dir().join(format!("assert_cli-slow-{}", process::id()));
+        let _ = fs::remove_file(&counter);
+        let script = "echo x >> \"$0\"; [ $(wc -l < \"$0\") -ne 2 ] || sleep 1";
+        let err = Assert::command(&["sh", "-c", script])
+            .with_args(&[&counter])
+            .check_flakiness(3)
+            .completes_within(Duration::from_millis(500))
+            .execute()
+            .unwrap_err();
+        let _ = fs::remove_file(&counter);
+        let message = Assert::format_causes(err.causes());
+        assert!(
+            message.contains("Outcomes differed across 3 runs:\n- completes within 500ms: held in runs 1, 3, failed in runs 2"),
+            "{}",
+            message
+        );
+    }
+
+    #[test]
+    fn stdin_file_is_relative_to_current_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("input"), "from dir").unwrap();
+        let assert = Assert::command(&["cat"])
+            .stdin_file("input")
+            .current_dir(dir.path());
+        let invocation = assert.dry_run().unwrap();
+        assert_eq!(invocation.stdin_len(), Some(8));
+        assert.deterministic(2).stdout().is("from dir").unwrap();
+    }
+
+    #[test]
+    fn deterministic_feeds_reader_input_to_every_run() {
+        Assert::command(&["cat"])
+            .stdin_reader(io::Cursor::new("hello"))
+            .deterministic(2)
+            .stdout()
+            .is("hello")
+            .unwrap();
+    }
+
+    #[test]
+    fn deterministic_runs_start_in_empty_directories() {
+        Assert::command(&["sh", "-c", "ls; touch leftover"])
+            .deterministic_with_files(2)
+            .stdout()
+            .is("")
+            .unwrap();
+    }
+
+    #[test]
+    fn deterministic_rejects_flakiness_check() {
+        let err = Assert::command(&["true"])
+            .check_flakiness(2)
+            .deterministic(2)
+            .execute()
+            .unwrap_err();
+        let message = Assert::format_causes(err.causes());
+        assert!(message.contains("can't be combined"), "{}", message);
+    }
+
+    #[test]
+    fn deterministic_shows_differing_files() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("input"), "a\n").unwrap();
+        let script = "cat input > output; date +%N >> output";
+        let err = Assert::command(&["sh", "-c", script])
+            .current_dir(dir.path())
+            .deterministic_with_files(2)
+            .execute()
+            .unwrap_err();
+        let message = Assert::format_causes(err.causes());
+        assert!(
+            message.contains("Run 2 differed from run 1:\noutput:\n"),
+            "{}",
+            message
+        );
+        assert!(!message.contains("input:"), "{}", message);
+        assert!(!dir.path().join("output").exists());
+    }
+
+    #[test]
+    fn exit_code_error_shows_accepted_codes() {
+        let err = Assert::command(&["sh", "-c", "exit 1"])
+            .exit_code_in(2..=9)
+            .execute()
+            .unwrap_err();
+        let message = Assert::format_causes(err.causes());
+        assert!(message.contains("expected=2..=9\ngot=1\n"), "{}", message);
+
+        let err = Assert::command(&["sh", "-c", "exit 2"])
+            .exit_code_one_of(&[0, 1])
+            .execute()
+            .unwrap_err();
+        let message = Assert::format_causes(err.causes());
+        assert!(
+            message.contains("expected=one of [0 (EX_OK), 1]\n"),
+            "{}",
+            message
+        );
+
+        Assert::command(&["true"]).exit_code_not(1).unwrap();
+
+        let err = Assert::command(&["sh", "-c", "exit 64"])
+            .fails_with_named("EX_DATAERR")
+            .execute()
+            .unwrap_err();
+        let message = Assert::format_causes(err.causes());
+        assert!(
+            message.contains("expected=65 (EX_DATAERR)\ngot=64 (EX_USAGE)\n"),
+            "{}",
+            message
+        );
+
+        let marker = env::temp_dir().join(format!("assert_cli-named-{}", process::id()));
+        let _ = fs::remove_file(&marker);
+        let err = Assert::command(&["touch"])
+            .with_args(&[&marker])
+            .check_flakiness(2)
+            .fails_with_named("EX_USGAE")
+            .execute()
+            .unwrap_err();
+        assert!(!marker.exists());
+        let message = Assert::format_causes(err.causes());
+        assert!(
+            message.ends_with("with: No exit code is named `EX_USGAE`"),
+            "{}",
+            message
+        );
+    }
+
+    #[test]
+    fn ansi_assertions_explain_failures() {
+        let colored =
+            Assert::command(&["printf", "\\033[33mwarning\\033[0m: \\033[31merror\\033[0m"]);
+
+        let err = colored.stdout().has_no_ansi().execute().unwrap_err();
+        let message = Assert::format_causes(err.causes());
+        assert!(message.contains("found one at byte 0."), "{}", message);
+        assert!(message.contains("```\\x1b[33mwarning"), "{}", message);
+
+        let colored =
+            Assert::command(&["printf", "\\033[33mwarning\\033[0m: \\033[31merror\\033[0m"]);
+        let err = colored
+            .stdout()
+            .styled_contains("warning", Style::red())
+            .execute()
+            .unwrap_err();
+        let message = Assert::format_causes(err.causes());
+        assert!(
+            message.contains("Expected ```warning``` in red, found it yellow.\noutput without escapes=```warning: error```"),
+            "{}",
+            message
+        );
+
+        Assert::command(&["printf", "\\033[1mwarning\\033[0m: \\033[31merror\\033[0m"])
+            .stdout()
+            .styled_contains("error", Style::red())
+            .stdout()
+            .stripped()
+            .contains_then_stop("warning: e")
+            .unwrap();
+    }
+
     #[test]
     fn take_string_i32() {
         command()
@@ -0,0 +1,548 @@
+use std::future::Future;
+use std::io::{self, Read, Write};
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::process::ExitStatus;
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::process::{self, Child, ChildStdin};
+use tokio::sync::mpsc;
+use tokio::task;
+use tokio::time::{self, Sleep};
+
+use assert::{Assert, Attempt, Outcome, Prepared, Run, Watcher};
+use capture::{Captured, Collected, Collector};
+use errors::{AssertionError, ChainFail};
+use invocation::Invocation;
+use output::OutputKind;
+use stdin::Stdin;
+
+impl Assert {
+    /// Execute the command and check the assertions without blocking the async runtime.
+    ///
+    /// The command is spawned with tokio's process API, and its stdin written and output read
+    /// as the runtime polls the returned future, so other tasks, like an in-process server the
+    /// command talks to, keep running.  Input from a reader or generator is produced on the
+    /// blocking thread pool.  Failures are the same as with [`execute`](#method.execute), except
+    /// that resource usage is not collected.
+    ///
+    /// Requires the `tokio` feature, and a runtime with IO and time enabled.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// extern crate assert_cli;
+    /// extern crate tokio;
+    ///
+    /// let runtime = tokio::runtime::Builder::new_current_thread()
+    ///     .enable_all()
+    ///     .build()
+    ///     .unwrap();
+    /// let test = runtime.block_on(
+    ///     assert_cli::Assert::command(&["cat"])
+    ///         .stdin("42")
+    ///         .stdout().contains("42")
+    ///         .execute_async(),
+    /// );
+    /// assert!(test.is_ok());
+    /// ```
+    pub fn execute_async(self) -> ExecuteAsync {
+        let backoff = self.backoff();
+        ExecuteAsync {
+            assert: self,
+            started: false,
+            failures: vec![],
+            backoff,
+            attempt: None,
+            state: State::Ready,
+        }
+    }
+}
+
+/// Future returned by [`Assert::execute_async`](struct.Assert.html#method.execute_async).
+#[must_use = "futures do nothing unless polled"]
+pub struct ExecuteAsync {
+    assert: Assert,
+    /// Whether the settings were checked, which happens on the first poll.
+    started: bool,
+    failures: Vec<AssertionError>,
+    backoff: Duration,
+    attempt: Option<Attempt>,
+    state: State,
+}
+
+enum State {
+    /// About to start the next run.
+    Ready,
+    Running(Box<RunAsync>),
+    /// Waiting to retry after a failed attempt.
+    Retrying(Pin<Box<Sleep>>),
+}
+
+impl Future for ExecuteAsync {
+    type Output = Result<Outcome, AssertionError>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let this = &mut *self;
+        if !this.started {
+            this.started = true;
+            if let Err(err) = this.assert.prepare_execution() {
+                return Poll::Ready(Err(err));
+            }
+        }
+        loop {
+            let run = match this.state {
+                State::Ready => {
+                    let assert = &mut this.assert;
+                    let attempt = this.attempt.get_or_insert_with(|| Attempt::new(assert));
+                    match attempt.before_run(assert) {
+                        Ok(()) => match RunAsync::start(assert) {
+                            Ok(run) => {
+                                this.state = State::Running(Box::new(run));
+                                continue;
+                            }
+                            Err(err) => Err(err),
+                        },
+                        Err(err) => {
+                            if let Err(err) = this.retry(err) {
+                                return Poll::Ready(Err(err));
+                            }
+                            continue;
+                        }
+                    }
+                }
+                State::Running(ref mut run) => match run.poll_run(cx, &this.assert) {
+                    Poll::Ready(run) => run,
+                    Poll::Pending => return Poll::Pending,
+                },
+                State::Retrying(ref mut sleep) => match sleep.as_mut().poll(cx) {
+                    Poll::Ready(()) => {
+                        this.state = State::Ready;
+                        continue;
+                    }
+                    Poll::Pending => return Poll::Pending,
+                },
+            };
+
+            this.state = State::Ready;
+            let attempt = this.attempt.as_mut().expect("created before the run");
+            match attempt.after_run(&mut this.assert, run) {
+                Ok(Some(outcome)) => return Poll::Ready(Ok(outcome)),
+                Ok(None) => {}
+                Err(err) => {
+                    if let Err(err) = this.retry(err) {
+                        return Poll::Ready(Err(err));
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl ExecuteAsync {
+    /// Schedule the next attempt after a failed one, or give up with the final error.
+    fn retry(&mut self, err: AssertionError) -> Result<(), AssertionError> {
+        self.attempt = None;
+        let wait = self
+            .assert
+            .retry_after(&mut self.failures, &mut self.backoff, err)?;
+        self.state = State::Retrying(Box::pin(time::sleep(wait)));
+        Ok(())
+    }
+}
+
+/// A stream of the command's output: one of its pipes, or a terminal.
+struct Stream {
+    kind: OutputKind,
+    reader: Box<dyn AsyncRead + Send + Unpin>,
+}
+
+/// A single run of the command, driven by the future's polls.
+struct RunAsync {
+    child: Child,
+    invocation: Option<Invocation>,
+    current_dir: Option<PathBuf>,
+    start: Instant,
+    /// Streams not read to the end yet, none once the command was killed.
+    streams: Vec<Stream>,
+    feed: Option<Feed>,
+    collector: Option<Collector>,
+    watcher: Watcher,
+    deadline: Option<Pin<Box<Sleep>>>,
+    stopped: bool,
+    overflowed: bool,
+    timed_out: bool,
+}
+
+impl RunAsync {
+    fn start(assert: &mut Assert) -> Result<Self, AssertionError> {
+        let Prepared {
+            invocation,
+            command,
+            stdin,
+            ptys,
+            current_dir,
+            limit,
+        } = assert.prepare()?;
+
+        let mut command = process::Command::from(command);
+        command.kill_on_drop(true);
+        let start = Instant::now();
+        let mut child = match command.spawn() {
+            Ok(child) => child,
+            Err(err) => return Err(assert.spawn_error(err, invocation)),
+        };
+        // Close our copies of the terminals, so reading them ends when the command exits.
+        drop(command);
+
+        let mut streams = vec![];
+        for (kind, pty) in ptys {
+            let master = tokio::fs::File::from_std(pty.into_master());
+            streams.push(Stream {
+                kind,
+                reader: Box::new(PtyStream(master)),
+            });
+        }
+        if let Some(stdout) = child.stdout.take() {
+            streams.push(Stream {
+                kind: OutputKind::StdOut,
+                reader: Box::new(stdout),
+            });
+        }
+        if let Some(stderr) = child.stderr.take() {
+            streams.push(Stream {
+                kind: OutputKind::StdErr,
+                reader: Box::new(stderr),
+            });
+        }
+
+        let feed = Feed::new(stdin, child.stdin.take());
+        Ok(RunAsync {
+            child,
+            invocation: Some(invocation),
+            current_dir,
+            start,
+            streams,
+            feed,
+            collector: Some(Collector::new(limit)),
+            watcher: Watcher::new(assert),
+            deadline: limit.timeout.map(|timeout| Box::pin(time::sleep(timeout))),
+            stopped: false,
+            overflowed: false,
+            timed_out: false,
+        })
+    }
+
+    /// Feed stdin and read the output until the command exited, or was killed.
+    fn poll_run(&mut self, cx: &mut Context, assert: &Assert) -> Poll<Result<Run, AssertionError>> {
+        match self.poll_exit(cx, assert) {
+            Poll::Ready(Ok(status)) => {
+                let collector = self.collector.take().expect("polled after completion");
+                let captured = Captured {
+                    stopped: self.stopped,
+                    overflowed: self.overflowed,
+                    timed_out: self.timed_out,
+                    ..collector.finish(status, None)
+                };
+                Poll::Ready(Ok(Run {
+                    captured,
+                    early_failure: self.watcher.early_failure.take(),
+                    elapsed: self.start.elapsed(),
+                    current_dir: self.current_dir.take(),
+                    invocation: self.invocation.take(),
+                }))
+            }
+            Poll::Ready(Err(err)) => {
+                Poll::Ready(Err(AssertionError::new(assert.cmd().to_vec()).chain(err)))
+            }
+            Poll::Pending => Poll::Pending,
+        }
+    }
+
+    fn poll_exit(&mut self, cx: &mut Context, assert: &Assert) -> Poll<io::Result<ExitStatus>> {
+        if let Some(ref mut deadline) = self.deadline {
+            if deadline.as_mut().poll(cx).is_ready() {
+                self.deadline = None;
+                self.timed_out = true;
+                self.kill()?;
+            }
+        }
+
+        if let Some(ref mut feed) = self.feed {
+            match feed.poll_feed(cx) {
+                Poll::Ready(Ok(())) => self.feed = None,
+                Poll::Ready(Err(ref err)) if err.kind() == io::ErrorKind::BrokenPipe => {
+                    self.feed = None
+                }
+                Poll::Ready(Err(err)) => return Poll::Ready(Err(err)),
+                Poll::Pending => {}
+            }
+        }
+
+        while let Some(collected) = self.poll_streams(cx, assert)? {
+            match collected {
+                Collected::Kept => continue,
+                Collected::Overflowed => self.overflowed = true,
+                // Don't wait for the pipes to close, grandchildren may still hold them open.
+                Collected::Stopped => self.stopped = true,
+            }
+            self.kill()?;
+        }
+        if !self.streams.is_empty() {
+            return Poll::Pending;
+        }
+
+        // A fresh `wait` each time is fine, tokio documents it as cancel safe.
+        let status = {
+            let wait = self.child.wait();
+            ::std::pin::pin!(wait).poll(cx)
+        };
+        match status {
+            Poll::Ready(Ok(status)) if self.feed.is_none() => Poll::Ready(Ok(status)),
+            // Still writing to a pipe nobody reads, which fails once the command is gone.
+            Poll::Ready(Ok(_)) => Poll::Pending,
+            Poll::Ready(Err(err)) => Poll::Ready(Err(err)),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+
+    /// Read a chunk from the first stream that has one, `None` when all are pending or done.
+    fn poll_streams(&mut self, cx: &mut Context, assert: &Assert) -> io::Result<Option<Collected>> {
+        let mut chunk = [0; 8192];
+        let mut i = 0;
+        while i < self.streams.len() {
+            let kind = self.streams[i].kind;
+            let mut buf = ReadBuf::new(&mut chunk);
+            match Pin::new(&mut self.streams[i].reader).poll_read(cx, &mut buf) {
+                Poll::Ready(Ok(())) if buf.filled().is_empty() => {
+                    self.streams.remove(i);
+                }
+                Poll::Ready(Ok(())) => {
+                    let collector = self.collector.as_mut().expect("polled after completion");
+                    let watcher = &mut self.watcher;
+                    let collected = collector.add(kind, buf.filled(), |kind, got, seen| {
+                        watcher.watch(assert, kind, got, seen)
+                    });
+                    return Ok(Some(collected));
+                }
+                Poll::Ready(Err(ref err)) if err.kind() == io::ErrorKind::Interrupted => {}
+                Poll::Ready(Err(err)) => return Err(err),
+                Poll::Pending => i += 1,
+            }
+        }
+        Ok(None)
+    }
+
+    /// Kill the command, and stop reading what it may still write.
+    fn kill(&mut self) -> io::Result<()> {
+        self.streams.clear();
+        match self.child.start_kill() {
+            // Already exited.
+            Err(ref err) if err.kind() == io::ErrorKind::InvalidInput => Ok(()),
+            result => result,
+        }
+    }
+}
+
+/// A terminal's output, which ends with `EIO` once the command closed its end.
+struct PtyStream(tokio::fs::File);
+
+impl AsyncRead for PtyStream {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context,
+        buf: &mut ReadBuf,
+    ) -> Poll<io::Result<()>> {
+        match Pin::new(&mut self.0).poll_read(cx, buf) {
+            #[cfg(unix)]
+            Poll::Ready(Err(ref e)) if e.raw_os_error() == Some(::libc::EIO) => Poll::Ready(Ok(())),
+            result => result,
+        }
+    }
+}
+
+/// Writes stdin as the command reads it.
+///
+/// Readers and generators block, so they run on the blocking thread pool, handing their output
+/// over in chunks.
+struct Feed {
+    pipe: ChildStdin,
+    pending: Vec<u8>,
+    written: usize,
+    source: Option<mpsc::Receiver<io::Result<Vec<u8>>>>,
+}
+
+impl Feed {
+    fn new(stdin: Stdin, pipe: Option<ChildStdin>) -> Option<Self> {
+        let pipe = pipe?;
+        let (pending, source) = match stdin {
+            Stdin::Bytes(contents) => (contents, None),
+            Stdin::Reader(mut reader) => {
+                let (sender, receiver) = mpsc::channel(4);
+                task::spawn_blocking(move || {
+                    let mut chunk = vec![0; 8192];
+                    loop {
+                        let result = match reader.read(&mut chunk) {
+                            Ok(0) => return,
+                            Ok(n) => Ok(chunk[..n].to_vec()),
+                            Err(ref err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                            Err(err) => Err(err),
+                        };
+                        let failed = result.is_err();
+                        if sender.blocking_send(result).is_err() || failed {
+                            return;
+                        }
+                    }
+                });
+                (vec![], Some(receiver))
+            }
+            Stdin::Generator(generate) => {
+                let (sender, receiver) = mpsc::channel(4);
+                task::spawn_blocking(move || {
+                    let mut writer = ChannelWriter(sender.clone());
+                    if let Err(err) = generate(&mut writer) {
+                        let _ = sender.blocking_send(Err(err));
+                    }
+                });
+                (vec![], Some(receiver))
+            }
+            // Dropping the pipe closes it.
+            _ => return None,
+        };
+        Some(Feed {
+            pipe,
+            pending,
+            written: 0,
+            source,
+        })
+    }
+
+    /// Write until the input ran out, dropping the pipe then closes it.
+    fn poll_feed(&mut self, cx: &mut Context) -> Poll<io::Result<()>> {
+        loop {
+            if self.written < self.pending.len() {
+                match Pin::new(&mut self.pipe).poll_write(cx, &self.pending[self.written..]) {
+                    Poll::Ready(Ok(0)) => {
+                        return Poll::Ready(Err(io::ErrorKind::WriteZero.into()));
+                    }
+                    Poll::Ready(Ok(n)) => self.written += n,
+                    Poll::Ready(Err(err)) => return Poll::Ready(Err(err)),
+                    Poll::Pending => return Poll::Pending,
+                }
+                continue;
+            }
+            let source = match self.source {
+                Some(ref mut source) => source,
+                None => return Poll::Ready(Ok(())),
+            };
+            match source.poll_recv(cx) {
+                Poll::Ready(Some(Ok(chunk))) => {
+                    self.pending = chunk;
+                    self.written = 0;
+                }
+                Poll::Ready(Some(Err(err))) => return Poll::Ready(Err(err)),
+                Poll::Ready(None) => return Poll::Ready(Ok(())),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+}
+
+/// Hands a generator's output over to the `Feed`.
+struct ChannelWriter(mpsc::Sender<io::Result<Vec<u8>>>);
+
+impl Write for ChannelWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0
+            .blocking_send(Ok(buf.to_vec()))
+            .map_err(|_| io::Error::from(io::ErrorKind::BrokenPipe))?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use failure::Fail;
+    use tokio::runtime;
+
+    fn runtime() -> runtime::Runtime {
+        runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn execute_async_yields_assertion_error() {
+        let err = runtime()
+            .block_on(
+                Assert::command(&["echo", "42"])
+                    .stdout()
+                    .is("43")
+                    .execute_async(),
+            )
+            .unwrap_err();
+        let message = Assert::format_causes(err.causes());
+        assert!(message.contains("Unexpected stdout"), "{}", message);
+    }
+
+    #[test]
+    fn execute_async_feeds_stdin() {
+        let input: Vec<u8> = b"line\n".iter().cycle().take(1 << 20).cloned().collect();
+        runtime()
+            .block_on(
+                Assert::command(&["wc", "-c"])
+                    .stdin_reader(io::Cursor::new(input))
+                    .stdout()
+                    .contains("1048576")
+                    .execute_async(),
+            )
+            .unwrap();
+    }
+
+    #[test]
+    fn execute_async_leaves_runtime_free() {
+        let runtime = runtime();
+        let local = task::LocalSet::new();
+        let start = Instant::now();
+        let first = local.spawn_local(Assert::command(&["sleep", "0.5"]).execute_async());
+        local
+            .block_on(&runtime, Assert::command(&["sleep", "0.5"]).execute_async())
+            .unwrap();
+        local.block_on(&runtime, first).unwrap().unwrap();
+        assert!(start.elapsed() < Duration::from_millis(900));
+    }
+
+    #[test]
+    fn execute_async_kills_on_timeout() {
+        let err = runtime()
+            .block_on(
+                Assert::command(&["sleep", "5"])
+                    .completes_within(Duration::from_millis(200))
+                    .execute_async(),
+            )
+            .unwrap_err();
+        let message = Assert::format_causes(err.causes());
+        assert!(message.contains("200ms"), "{}", message);
+    }
+
+    #[test]
+    fn execute_async_reads_terminal() {
+        runtime()
+            .block_on(
+                Assert::command(&["sh", "-c", "test -t 1 && stty size <&1"])
+                    .tty_stdout(24, 100)
+                    .stdout()
+                    .is("24 100")
+                    .execute_async(),
+            )
+            .unwrap();
+    }
+}
@@ -0,0 +1,285 @@
+use std::any::Any;
+use std::fmt;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use failure::{self, Fail};
+
+use assert::{Assert, Outcome};
+use errors::{AssertionError, ChainFail};
+
+/// Executes many `Assert`s concurrently and reports on all of them.
+///
+/// Each case is given as a closure building its `Assert`, called on the thread executing it,
+/// so assertions like [`satisfies`](struct.OutputAssertionBuilder.html#method.satisfies) don't
+/// need to be `Send`.  A case that panics fails with the panic's message, without affecting
+/// the others.
+///
+/// # Examples
+///
+/// ```rust
+/// extern crate assert_cli;
+/// use assert_cli::{Assert, Batch};
+///
+/// let report = ["1", "2", "3"]
+///     .iter()
+///     .fold(Batch::new().workers(2), |batch, &n| {
+///         batch.case(n, move || Assert::command(&["echo", n]).stdout().is(n))
+///     })
+///     .run();
+/// assert_eq!(report.passed(), 3);
+/// report.unwrap();
+/// ```
+#[must_use]
+pub struct Batch {
+    cases: Vec<(String, Case)>,
+    workers: usize,
+}
+
+type Case = Box<dyn FnOnce() -> Assert + Send>;
+
+impl fmt::Debug for Batch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let names: Vec<_> = self.cases.iter().map(|(name, _)| name).collect();
+        f.debug_struct("Batch")
+            .field("cases", &names)
+            .field("workers", &self.workers)
+            .finish()
+    }
+}
+
+impl Default for Batch {
+    fn default() -> Self {
+        Self {
+            cases: vec![],
+            workers: thread::available_parallelism().map_or(1, |n| n.get()),
+        }
+    }
+}
+
+impl Batch {
+    /// Create an empty batch, running as many cases at once as there are CPUs.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Run at most `workers` cases at once.
+    pub fn workers(mut self, workers: usize) -> Self {
+        self.workers = workers.max(1);
+        self
+    }
+
+    /// Add a case, named for the report, built by `assert` when it is executed.
+    pub fn case<N, F>(mut self, name: N, assert: F) -> Self
+    where
+        N: Into<String>,
+        F: FnOnce() -> Assert + Send + 'static,
+    {
+        self.cases.push((name.into(), Box::new(assert)));
+        self
+    }
+
+    /// Execute all cases, returning once each finished.
+    pub fn run(self) -> BatchReport {
+        let total = self.cases.len();
+        let queue = Arc::new(Mutex::new(self.cases.into_iter().enumerate()));
+        let (sender, receiver) = mpsc::channel();
+        let workers: Vec<_> = (0..self.workers.min(total))
+            .map(|_| {
+                let queue = Arc::clone(&queue);
+                let sender = sender.clone();
+                thread::spawn(move || loop {
+                    let next = queue.lock().unwrap_or_else(|e| e.into_inner()).next();
+                    let (i, (name, assert)) = match next {
+                        Some(next) => next,
+                        None => break,
+                    };
+                    if sender.send((i, name, execute(assert))).is_err() {
+                        break;
+                    }
+                })
+            })
+            .collect();
+        drop(sender);
+
+        let mut results: Vec<_> = receiver.iter().collect();
+        for worker in workers {
+            worker.join().expect("batch worker panicked");
+        }
+        results.sort_by_key(|&(i, _, _)| i);
+        BatchReport {
+            results: results
+                .into_iter()
+                .map(|(_, name, result)| (name, result))
+                .collect(),
+        }
+    }
+}
+
+/// Build and execute a case, turning a panic into its failure.
+fn execute(assert: Case) -> Result<Outcome, AssertionError> {
+    let mut cmd = vec![];
+    panic::catch_unwind(AssertUnwindSafe(|| {
+        let assert = assert();
+        cmd = assert.cmd().to_vec();
+        assert.execute()
+    }))
+    .unwrap_or_else(|payload| {
+        let msg = format!("Panicked: {}", panic_message(&*payload));
+        Err(AssertionError::new(cmd).chain(failure::err_msg(msg)))
+    })
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> &str {
+    match payload.downcast_ref::<&str>() {
+        Some(msg) => msg,
+        None => payload
+            .downcast_ref::<String>()
+            .map_or("Box<dyn Any>", String::as_str),
+    }
+}
+
+/// Results of a [`Batch`](struct.Batch.html), in the order the cases were added.
+///
+/// Displays as a table of passes and failures, followed by the cause of each failure.
+#[derive(Debug)]
+pub struct BatchReport {
+    results: Vec<(String, Result<Outcome, AssertionError>)>,
+}
+
+impl BatchReport {
+    /// Each case's name and result.
+    pub fn results(&self) -> &[(String, Result<Outcome, AssertionError>)] {
+        &self.results
+    }
+
+    /// How many cases passed.
+    pub fn passed(&self) -> usize {
+        self.results.iter().filter(|(_, r)| r.is_ok()).count()
+    }
+
+    /// How many cases failed.
+    pub fn failed(&self) -> usize {
+        self.results.len() - self.passed()
+    }
+
+    /// Whether all cases passed.
+    pub fn is_success(&self) -> bool {
+        self.failed() == 0
+    }
+
+    /// Panic with the report if any case failed.
+    pub fn unwrap(self) {
+        if !self.is_success() {
+            panic!("{}", self);
+        }
+    }
+}
+
+impl fmt::Display for BatchReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let width = self
+            .results
+            .iter()
+            .map(|(name, _)| name.chars().count())
+            .max()
+            .unwrap_or(0);
+        for (name, result) in &self.results {
+            let (status, elapsed) = match *result {
+                Ok(ref outcome) => ("PASS", Some(outcome.elapsed())),
+                Err(ref err) => ("FAIL", err.elapsed()),
+            };
+            write!(f, "{}  {:width$}", status, name, width = width)?;
+            match elapsed {
+                Some(elapsed) => writeln!(f, "  {}", format_elapsed(elapsed))?,
+                None => writeln!(f, "  -")?,
+            }
+        }
+        write!(f, "{} passed, {} failed", self.passed(), self.failed())?;
+
+        for (name, result) in &self.results {
+            if let Err(ref err) = *result {
+                let chain = (err as &dyn Fail).iter_chain();
+                write!(
+                    f,
+                    "\n\n---- {} ----\n{}",
+                    name,
+                    Assert::format_causes(chain)
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn format_elapsed(elapsed: Duration) -> String {
+    format!("{:.1?}", elapsed)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn report_lists_failures_in_order() {
+        let report = Batch::new()
+            .workers(3)
+            .case("first", || Assert::command(&["true"]))
+            .case("second", || Assert::command(&["false"]))
+            .case("third", || {
+                Assert::command(&["echo", "42"])
+                    .stdout()
+                    .satisfies(|out| out.trim() == "43", "not 43")
+            })
+            .case("fourth", || Assert::command(&["true"]))
+            .run();
+        assert_eq!(report.passed(), 2);
+        assert_eq!(report.failed(), 2);
+
+        let names: Vec<_> = report.results().iter().map(|(n, _)| n.as_str()).collect();
+        assert_eq!(names, ["first", "second", "third", "fourth"]);
+
+        let summary = report.to_string();
+        assert!(summary.contains("FAIL  second"), "{}", summary);
+        assert!(summary.contains("PASS  fourth"), "{}", summary);
+        assert!(summary.contains("2 passed, 2 failed"), "{}", summary);
+        assert!(summary.contains("---- third ----\nAssertion failed for `echo 42`"));
+        assert!(summary.contains("not 43"), "{}", summary);
+    }
+
+    #[test]
+    fn panicking_case_fails_alone() {
+        let report = Batch::new()
+            .workers(1)
+            .case("build", || panic!("no fixture"))
+            .case("predicate", || {
+                Assert::command(&["echo", "42"])
+                    .stdout()
+                    .satisfies(|_| panic!("bad predicate"), "never")
+            })
+            .case("pass", || Assert::command(&["true"]))
+            .run();
+        assert_eq!(report.passed(), 1);
+
+        let summary = report.to_string();
+        assert!(
+            summary.contains("---- build ----\nAssertion failed\nwith: Panicked: no fixture"),
+            "{}",
+            summary
+        );
+        assert!(
+            summary.contains("for `echo 42`\nwith: Panicked: bad predicate"),
+            "{}",
+            summary
+        );
+    }
+
+    #[test]
+    fn empty_batch_succeeds() {
+        let report = Batch::new().run();
+        assert!(report.is_success());
+        assert_eq!(report.to_string(), "0 passed, 0 failed");
+    }
+}
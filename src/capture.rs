@@ -0,0 +1,276 @@
+use std::io::{self, Read};
+use std::process::{self, Child};
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use output::OutputKind;
+use rusage::{self, ResourceUsage};
+
+/// What to do with the command after a chunk of output was seen.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Watch {
+    Continue,
+    Stop,
+}
+
+enum Event {
+    Chunk(OutputKind, Vec<u8>),
+    Done(io::Result<()>),
+}
+
+/// How much of each stream to keep, and how long to let the command run.
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct Limit {
+    pub max_bytes: Option<usize>,
+    /// Kill the command once a stream exceeds `max_bytes`, instead of discarding the rest.
+    pub kill: bool,
+    pub timeout: Option<Duration>,
+}
+
+/// Output of a command, and whether it was killed before it exited on its own.
+#[derive(Debug)]
+pub(crate) struct Captured {
+    pub output: process::Output,
+    /// Killed because `watch` asked to stop.
+    pub stopped: bool,
+    /// Killed because a stream exceeded its limit.
+    pub overflowed: bool,
+    /// Killed because it ran past the timeout.
+    pub timed_out: bool,
+    /// Resources used by the command, where supported.
+    pub usage: Option<ResourceUsage>,
+    /// Bytes written to stdout, including those beyond the limit.
+    pub stdout_len: usize,
+    /// Bytes written to stderr, including those beyond the limit.
+    pub stderr_len: usize,
+}
+
+impl Captured {
+    pub fn len(&self, kind: OutputKind) -> usize {
+        match kind {
+            OutputKind::StdOut => self.stdout_len,
+            OutputKind::StdErr => self.stderr_len,
+        }
+    }
+}
+
+/// What became of a chunk of output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Collected {
+    Kept,
+    /// The stream exceeded its limit, and the command should be killed.
+    Overflowed,
+    /// `watch` asked to stop the command.
+    Stopped,
+}
+
+/// The output kept so far, shared by the blocking and the async capture.
+#[derive(Debug)]
+pub(crate) struct Collector {
+    limit: Limit,
+    stdout: Vec<u8>,
+    stderr: Vec<u8>,
+    stdout_len: usize,
+    stderr_len: usize,
+}
+
+impl Collector {
+    pub fn new(limit: Limit) -> Self {
+        Self {
+            limit,
+            stdout: vec![],
+            stderr: vec![],
+            stdout_len: 0,
+            stderr_len: 0,
+        }
+    }
+
+    /// Keep what fits of `chunk`, and let `watch` look at the grown stream.
+    pub fn add<F>(&mut self, kind: OutputKind, chunk: &[u8], watch: F) -> Collected
+    where
+        F: FnOnce(OutputKind, &[u8], usize) -> Watch,
+    {
+        let (buffer, len) = match kind {
+            OutputKind::StdOut => (&mut self.stdout, &mut self.stdout_len),
+            OutputKind::StdErr => (&mut self.stderr, &mut self.stderr_len),
+        };
+        *len += chunk.len();
+        let seen = buffer.len();
+        let room = self
+            .limit
+            .max_bytes
+            .map_or(chunk.len(), |max| max.saturating_sub(seen).min(chunk.len()));
+        buffer.extend_from_slice(&chunk[..room]);
+        if room < chunk.len() && self.limit.kill {
+            return Collected::Overflowed;
+        }
+        if room > 0 && watch(kind, buffer, seen) == Watch::Stop {
+            return Collected::Stopped;
+        }
+        Collected::Kept
+    }
+
+    pub fn finish(self, status: process::ExitStatus, usage: Option<ResourceUsage>) -> Captured {
+        Captured {
+            output: process::Output {
+                status,
+                stdout: self.stdout,
+                stderr: self.stderr,
+            },
+            stopped: false,
+            overflowed: false,
+            timed_out: false,
+            usage,
+            stdout_len: self.stdout_len,
+            stderr_len: self.stderr_len,
+        }
+    }
+}
+
+/// Collect the child's stdout and stderr, calling `watch` with the output of a stream whenever
+/// it grew, along with how much of it `watch` has already seen.
+///
+/// When `watch` asks to stop, the child is killed and the output captured so far is returned.
+/// Output beyond `limit` is dropped, and `watch` no longer called for that stream.
+///
+/// `streams` are read in place of the child's pipes, like terminals the child writes to.
+pub(crate) fn capture<F>(
+    child: &mut Child,
+    streams: Vec<(OutputKind, Box<dyn Read + Send>)>,
+    limit: Limit,
+    mut watch: F,
+) -> io::Result<Captured>
+where
+    F: FnMut(OutputKind, &[u8], usize) -> Watch,
+{
+    let (sender, receiver) = mpsc::channel();
+    let mut open = 0;
+    for (kind, stream) in streams {
+        read_on_thread(kind, stream, sender.clone());
+        open += 1;
+    }
+    if let Some(stdout) = child.stdout.take() {
+        read_on_thread(OutputKind::StdOut, stdout, sender.clone());
+        open += 1;
+    }
+    if let Some(stderr) = child.stderr.take() {
+        read_on_thread(OutputKind::StdErr, stderr, sender.clone());
+        open += 1;
+    }
+    drop(sender);
+
+    let mut collector = Collector::new(limit);
+    let mut stopped = false;
+    let mut overflowed = false;
+    let mut timed_out = false;
+    let deadline = limit.timeout.map(|timeout| Instant::now() + timeout);
+    while open > 0 {
+        let event = match deadline {
+            Some(deadline) => {
+                let remaining = deadline.saturating_duration_since(Instant::now());
+                match receiver.recv_timeout(remaining) {
+                    Err(mpsc::RecvTimeoutError::Timeout) => {
+                        timed_out = true;
+                        kill(child)?;
+                        break;
+                    }
+                    event => event.map_err(|_| mpsc::RecvError),
+                }
+            }
+            None => receiver.recv(),
+        };
+        match event {
+            Ok(Event::Chunk(kind, chunk)) => match collector.add(kind, &chunk, &mut watch) {
+                Collected::Kept => {}
+                Collected::Overflowed => {
+                    overflowed = true;
+                    kill(child)?;
+                    break;
+                }
+                Collected::Stopped => {
+                    // Don't wait for the pipes to close, grandchildren may still hold them open.
+                    stopped = true;
+                    kill(child)?;
+                    break;
+                }
+            },
+            Ok(Event::Done(result)) => {
+                result?;
+                open -= 1;
+            }
+            Err(_) => break,
+        }
+    }
+
+    let (status, usage) = match deadline {
+        Some(deadline) if !timed_out => {
+            let (exit, late) = wait_until(child, deadline)?;
+            timed_out = late;
+            exit
+        }
+        _ => wait(child)?,
+    };
+    Ok(Captured {
+        stopped,
+        overflowed,
+        timed_out,
+        ..collector.finish(status, usage)
+    })
+}
+
+fn read_on_thread<R>(kind: OutputKind, mut pipe: R, sender: mpsc::Sender<Event>)
+where
+    R: Read + Send + 'static,
+{
+    thread::spawn(move || {
+        let mut buffer = [0; 8 * 1024];
+        let result = loop {
+            match pipe.read(&mut buffer) {
+                Ok(0) => break Ok(()),
+                Ok(n) => {
+                    if sender
+                        .send(Event::Chunk(kind, buffer[..n].to_vec()))
+                        .is_err()
+                    {
+                        break Ok(());
+                    }
+                }
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => break Err(e),
+            }
+        };
+        // The receiver is gone if the command was stopped.
+        let _ = sender.send(Event::Done(result));
+    });
+}
+
+type Exit = (process::ExitStatus, Option<ResourceUsage>);
+
+fn wait(child: &mut Child) -> io::Result<Exit> {
+    let exit = rusage::wait(child, true)?;
+    Ok(exit.expect("blocking wait returns once the child exited"))
+}
+
+/// Wait for the child, killing it at `deadline`.  Also returns whether it had to be killed.
+fn wait_until(child: &mut Child, deadline: Instant) -> io::Result<(Exit, bool)> {
+    loop {
+        if let Some(exit) = rusage::wait(child, false)? {
+            return Ok((exit, false));
+        }
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining == Duration::from_secs(0) {
+            kill(child)?;
+            return Ok((wait(child)?, true));
+        }
+        thread::sleep(remaining.min(Duration::from_millis(10)));
+    }
+}
+
+/// Kill the child, which may have exited in the meantime.
+fn kill(child: &mut Child) -> io::Result<()> {
+    match child.kill() {
+        Err(ref e) if e.kind() == io::ErrorKind::InvalidInput => Ok(()),
+        result => result,
+    }
+}
@@ -0,0 +1,92 @@
+use std::env;
+use std::io::{self, IsTerminal};
+
+/// When to use colors in assertion failures.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ColorChoice {
+    /// Color when `CLICOLOR_FORCE` is set to something other than `0`, or when stderr is a terminal
+    /// and `NO_COLOR` is unset or empty.
+    #[default]
+    Auto,
+    /// Always color.
+    Always,
+    /// Never color.
+    Never,
+}
+
+impl ColorChoice {
+    fn enabled(self) -> bool {
+        match self {
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+            ColorChoice::Auto => {
+                if env_flag("CLICOLOR_FORCE") == Some(true) {
+                    true
+                } else if env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty()) {
+                    false
+                } else {
+                    io::stderr().is_terminal()
+                }
+            }
+        }
+    }
+}
+
+/// Whether a variable is set, and to something other than `0`.
+fn env_flag(name: &str) -> Option<bool> {
+    env::var_os(name).map(|v| v != "0")
+}
+
+/// Applies SGR styles when coloring is enabled.
+#[derive(Debug, Clone, Copy)]
+pub struct Palette {
+    enabled: bool,
+}
+
+impl Palette {
+    pub fn new(choice: ColorChoice) -> Self {
+        Self {
+            enabled: choice.enabled(),
+        }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn red(&self, text: &str) -> String {
+        self.paint("31", text)
+    }
+
+    pub fn green(&self, text: &str) -> String {
+        self.paint("32", text)
+    }
+
+    pub fn green_reverse(&self, text: &str) -> String {
+        self.paint("7;32", text)
+    }
+
+    pub fn green_dimmed(&self, text: &str) -> String {
+        self.paint("2;32", text)
+    }
+
+    pub fn cyan(&self, text: &str) -> String {
+        self.paint("36", text)
+    }
+
+    pub fn red_reverse(&self, text: &str) -> String {
+        self.paint("7;31", text)
+    }
+
+    pub fn dimmed(&self, text: &str) -> String {
+        self.paint("2", text)
+    }
+
+    fn paint(&self, codes: &str, text: &str) -> String {
+        if self.enabled {
+            format!("\u{1b}[{}m{}\u{1b}[0m", codes, text)
+        } else {
+            text.to_owned()
+        }
+    }
+}
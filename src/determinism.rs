@@ -0,0 +1,136 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process;
+
+use diff::Renderer;
+
+/// Everything a run produced that should be the same in every run.
+#[derive(Debug)]
+pub(crate) struct Snapshot {
+    code: Option<i32>,
+    stdout: Vec<u8>,
+    stderr: Vec<u8>,
+    files: Option<BTreeMap<PathBuf, Vec<u8>>>,
+}
+
+impl Snapshot {
+    pub fn new(output: &process::Output, files: Option<BTreeMap<PathBuf, Vec<u8>>>) -> Self {
+        Self {
+            code: output.status.code(),
+            stdout: output.stdout.clone(),
+            stderr: output.stderr.clone(),
+            files,
+        }
+    }
+
+    /// How `other` differs from this run, rendered for humans.
+    pub fn differences(&self, other: &Snapshot, renderer: &Renderer) -> Vec<String> {
+        let mut differences = vec![];
+        if self.code != other.code {
+            differences.push(format!("exit code: {:?} vs {:?}", self.code, other.code));
+        }
+        if self.stdout != other.stdout {
+            differences.push(format!(
+                "stdout:\n{}",
+                render(&self.stdout, &other.stdout, renderer)
+            ));
+        }
+        if self.stderr != other.stderr {
+            differences.push(format!(
+                "stderr:\n{}",
+                render(&self.stderr, &other.stderr, renderer)
+            ));
+        }
+
+        let (files, other_files) = match (&self.files, &other.files) {
+            (Some(files), Some(other_files)) => (files, other_files),
+            _ => return differences,
+        };
+        for (path, contents) in files {
+            match other_files.get(path) {
+                None => differences.push(format!("{}: missing", path.display())),
+                Some(other) if other != contents => differences.push(format!(
+                    "{}:\n{}",
+                    path.display(),
+                    render(contents, other, renderer)
+                )),
+                Some(_) => {}
+            }
+        }
+        for path in other_files.keys().filter(|p| !files.contains_key(*p)) {
+            differences.push(format!("{}: unexpected", path.display()));
+        }
+        differences
+    }
+}
+
+pub(crate) fn render(first: &[u8], other: &[u8], renderer: &Renderer) -> String {
+    let rendered = match (
+        String::from_utf8(first.to_vec()),
+        String::from_utf8(other.to_vec()),
+    ) {
+        (Ok(first), Ok(other)) => renderer.render_text(&first, &other),
+        _ => renderer.render_bytes(first, other),
+    };
+    rendered.unwrap_or_default()
+}
+
+/// Copy the contents of `from` into the existing directory `to`.
+pub(crate) fn copy_dir(from: &Path, to: &Path) -> io::Result<()> {
+    for entry in fs::read_dir(from)? {
+        let entry = entry?;
+        let target = to.join(entry.file_name());
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            fs::create_dir(&target)?;
+            copy_dir(&entry.path(), &target)?;
+        } else if file_type.is_symlink() {
+            copy_link(&entry.path(), &target)?;
+        } else {
+            fs::copy(entry.path(), &target)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn copy_link(from: &Path, to: &Path) -> io::Result<()> {
+    ::std::os::unix::fs::symlink(fs::read_link(from)?, to)
+}
+
+#[cfg(not(unix))]
+fn copy_link(from: &Path, to: &Path) -> io::Result<()> {
+    fs::copy(from, to).map(|_| ())
+}
+
+/// The contents of each file below `dir`, by path relative to `dir`.
+///
+/// Symbolic links are recorded by their target.
+pub(crate) fn read_tree(dir: &Path) -> io::Result<BTreeMap<PathBuf, Vec<u8>>> {
+    let mut files = BTreeMap::new();
+    read_tree_into(dir, Path::new(""), &mut files)?;
+    Ok(files)
+}
+
+fn read_tree_into(
+    dir: &Path,
+    prefix: &Path,
+    files: &mut BTreeMap<PathBuf, Vec<u8>>,
+) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let path = prefix.join(entry.file_name());
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            read_tree_into(&entry.path(), &path, files)?;
+        } else if file_type.is_symlink() {
+            let target = fs::read_link(entry.path())?;
+            files.insert(path, target.to_string_lossy().into_owned().into_bytes());
+        } else {
+            files.insert(path, fs::read(entry.path())?);
+        }
+    }
+    Ok(())
+}
@@ -1,58 +1,574 @@
+use std::borrow::Cow;
+use std::env;
 use std::fmt::{Error as fmtError, Write};
 
-use colored::Colorize;
 use difference::{Changeset, Difference};
 
-pub fn render(&Changeset { ref diffs, .. }: &Changeset) -> Result<String, fmtError> {
-    let mut t = String::new();
+use color::{ColorChoice, Palette};
 
-    for (i, diff) in diffs.iter().enumerate() {
-        match *diff {
-            Difference::Same(ref x) => {
-                writeln!(t, " {}", x)?;
-            }
-            Difference::Rem(ref x) => {
-                writeln!(t, "{}", format!("-{}", x).red())?;
+/// Number of unchanged lines kept around a change before the rest is elided.
+const ELIDE_CONTEXT: usize = 3;
+
+/// Terminal width assumed when `COLUMNS` is not set.
+const DEFAULT_WIDTH: usize = 80;
+
+/// Number of bytes per hexdump row.
+const HEX_ROW: usize = 16;
+
+/// Line added after the last line of a text that doesn't end in a newline, like `diff -u` does.
+const NO_NEWLINE: &str = "\\ No newline at end of file";
+
+/// How mismatching output is rendered in assertion failures.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DiffStyle {
+    /// Print every line, eliding long runs of unchanged lines.
+    #[default]
+    Full,
+    /// Print `@@` hunks with `context` unchanged lines around each change.
+    Unified {
+        /// Number of unchanged lines shown before and after a change.
+        context: usize,
+    },
+    /// Print expected and actual output next to each other.
+    SideBySide {
+        /// Total width of both columns, defaults to `COLUMNS` or 80.
+        width: Option<usize>,
+    },
+    /// Print changed lines once, marking removed `[-words-]` and added `{+words+}`.
+    Inline,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Tag {
+    Same,
+    Rem,
+    Add,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Row<'a> {
+    Same(&'a str),
+    Changed(Option<&'a str>, Option<&'a str>),
+    Elided(usize),
+}
+
+/// Renders a `Changeset` according to a `DiffStyle` and `ColorChoice`.
+#[derive(Debug, Clone)]
+pub struct Renderer {
+    style: DiffStyle,
+    palette: Palette,
+}
+
+impl Renderer {
+    pub fn new(style: DiffStyle, color: ColorChoice) -> Self {
+        Self {
+            style,
+            palette: Palette::new(color),
+        }
+    }
+
+    pub fn render(&self, changeset: &Changeset) -> Result<String, fmtError> {
+        match self.style {
+            DiffStyle::Full => self.render_full(changeset),
+            DiffStyle::Unified { context } => self.render_unified(changeset, context),
+            DiffStyle::SideBySide { width } => {
+                let width = width.or_else(terminal_width).unwrap_or(DEFAULT_WIDTH);
+                self.render_side_by_side(changeset, width)
             }
-            Difference::Add(ref x) => {
-                if i == 0 {
-                    writeln!(t, "{}", format!("+{}", x).green())?;
+            DiffStyle::Inline => self.render_inline(changeset),
+        }
+    }
+
+    /// Render a line diff of `expected` and `got`, pointing out when only one of them ends in a
+    /// newline.
+    pub fn render_text(&self, expected: &str, got: &str) -> Result<String, fmtError> {
+        if expected.ends_with('\n') == got.ends_with('\n') {
+            return self.render(&Changeset::new(expected, got, "\n"));
+        }
+        let lines = |text: &str| match text.strip_suffix('\n') {
+            Some(text) => text.to_owned(),
+            None => format!("{}\n{}", text, NO_NEWLINE),
+        };
+        self.render(&Changeset::new(&lines(expected), &lines(got), "\n"))
+    }
+
+    /// Render a hexdump of `expected` and `got`, eliding identical rows far from a difference.
+    pub fn render_bytes(&self, expected: &[u8], got: &[u8]) -> Result<String, fmtError> {
+        let mut t = String::new();
+
+        let first = expected
+            .iter()
+            .zip(got)
+            .position(|(e, g)| e != g)
+            .or_else(|| {
+                if expected.len() == got.len() {
+                    None
                 } else {
-                    match diffs[i - 1] {
-                        Difference::Rem(ref y) => {
-                            write!(t, "{}", "+".green())?;
-                            let Changeset { diffs, .. } = Changeset::new(y, x, " ");
-                            for c in diffs {
-                                match c {
-                                    Difference::Same(ref z) if !z.is_empty() => {
-                                        write!(t, "{}", z.green())?;
-                                        write!(t, " ")?;
-                                    }
-                                    Difference::Add(ref z) if !z.is_empty() => {
-                                        write!(t, "{}", z.green().reverse())?;
-                                        write!(t, " ")?;
+                    Some(expected.len().min(got.len()))
+                }
+            });
+        write!(
+            t,
+            "expected {} bytes, got {} bytes",
+            expected.len(),
+            got.len()
+        )?;
+        match first {
+            Some(offset) => writeln!(t, ", first difference at offset {:#010x}", offset)?,
+            None => writeln!(t)?,
+        }
+
+        let rows = expected.len().max(got.len()).div_ceil(HEX_ROW);
+        let row = |data: &'_ [u8], r: usize| -> Vec<u8> {
+            data.iter()
+                .skip(r * HEX_ROW)
+                .take(HEX_ROW)
+                .cloned()
+                .collect()
+        };
+        let changed: Vec<bool> = (0..rows).map(|r| row(expected, r) != row(got, r)).collect();
+        let shown: Vec<bool> = (0..rows)
+            .map(|r| {
+                let from = r.saturating_sub(ELIDE_CONTEXT);
+                let to = (r + ELIDE_CONTEXT + 1).min(rows);
+                changed[from..to].iter().any(|&c| c)
+            })
+            .collect();
+
+        let mut r = 0;
+        while r < rows {
+            let hidden = shown[r..].iter().take_while(|&&s| !s).count();
+            if hidden > 1 {
+                writeln!(t, "{}", self.palette.dimmed(&elided_marker(hidden)))?;
+                r += hidden;
+                continue;
+            }
+
+            let (left, right) = (row(expected, r), row(got, r));
+            if !changed[r] {
+                writeln!(t, " {}", self.hex_row(r, &left, &right, None))?;
+            } else {
+                if !left.is_empty() {
+                    let hex = self.hex_row(r, &left, &right, Some(Palette::red_reverse));
+                    writeln!(t, "{}{}", self.palette.red("-"), hex)?;
+                }
+                if !right.is_empty() {
+                    let hex = self.hex_row(r, &right, &left, Some(Palette::green_reverse));
+                    writeln!(t, "{}{}", self.palette.green("+"), hex)?;
+                }
+                if !self.palette.enabled() {
+                    writeln!(t, " {}", hex_carets(&left, &right))?;
+                }
+            }
+            r += 1;
+        }
+
+        Ok(t)
+    }
+
+    /// Format one row as offset, hex and ASCII columns, highlighting bytes that differ from `other`.
+    fn hex_row(
+        &self,
+        r: usize,
+        data: &[u8],
+        other: &[u8],
+        highlight: Option<fn(&Palette, &str) -> String>,
+    ) -> String {
+        let mut hex = String::new();
+        let mut ascii = String::new();
+        for i in 0..HEX_ROW {
+            if i == HEX_ROW / 2 {
+                hex.push(' ');
+            }
+            let byte = match data.get(i) {
+                Some(&byte) => byte,
+                None => {
+                    hex.push_str("   ");
+                    continue;
+                }
+            };
+            let (h, a) = (format!("{:02x}", byte), printable(byte).to_string());
+            match highlight {
+                Some(paint) if other.get(i) != Some(&byte) => {
+                    hex.push_str(&paint(&self.palette, &h));
+                    ascii.push_str(&paint(&self.palette, &a));
+                }
+                _ => {
+                    hex.push_str(&h);
+                    ascii.push_str(&a);
+                }
+            }
+            hex.push(' ');
+        }
+        format!("{:08x}  {} |{}|", r * HEX_ROW, hex, ascii)
+    }
+
+    /// Without colors, changed lines would hide whitespace-only differences.
+    fn visible<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        if self.palette.enabled() {
+            Cow::Borrowed(text)
+        } else {
+            let lines: Vec<_> = text.split('\n').map(visible_whitespace).collect();
+            Cow::Owned(lines.join("\n"))
+        }
+    }
+
+    fn removed(&self, prefix: &str, text: &str) -> String {
+        self.palette
+            .red(&format!("{}{}", prefix, self.visible(text)))
+    }
+
+    fn added(&self, prefix: &str, text: &str) -> String {
+        self.palette
+            .green(&format!("{}{}", prefix, self.visible(text)))
+    }
+
+    fn render_full(&self, &Changeset { ref diffs, .. }: &Changeset) -> Result<String, fmtError> {
+        let mut t = String::new();
+
+        for (i, diff) in diffs.iter().enumerate() {
+            match *diff {
+                Difference::Same(ref x) => {
+                    let same = elide_same(x, i == 0, i == diffs.len() - 1, &self.palette);
+                    writeln!(t, " {}", same)?;
+                }
+                Difference::Rem(ref x) => {
+                    writeln!(t, "{}", self.removed("-", x))?;
+                }
+                Difference::Add(ref x) => {
+                    if i == 0 {
+                        writeln!(t, "{}", self.added("+", x))?;
+                    } else {
+                        match diffs[i - 1] {
+                            Difference::Rem(ref y) => {
+                                write!(t, "{}", self.palette.green("+"))?;
+                                let Changeset { diffs, .. } = Changeset::new(y, x, " ");
+                                for c in diffs {
+                                    match c {
+                                        Difference::Same(ref z) if !z.is_empty() => {
+                                            write!(t, "{}", self.palette.green(z))?;
+                                            write!(t, " ")?;
+                                        }
+                                        Difference::Add(ref z) if !z.is_empty() => {
+                                            let z = self.visible(z);
+                                            if self.palette.enabled() {
+                                                write!(t, "{}", self.palette.green_reverse(&z))?;
+                                            } else {
+                                                write!(t, "{{+{}+}}", z)?;
+                                            }
+                                            write!(t, " ")?;
+                                        }
+                                        _ => (),
                                     }
-                                    _ => (),
                                 }
+                                writeln!(t, "")?;
+                            }
+                            _ => {
+                                let added = format!("+{}", self.visible(x));
+                                writeln!(t, "{}", self.palette.green_dimmed(&added))?;
                             }
-                            writeln!(t, "")?;
-                        }
-                        _ => {
-                            writeln!(t, "{}", format!("+{}", x).green().dimmed())?;
-                        }
+                        };
+                    }
+                }
+            }
+        }
+
+        Ok(t)
+    }
+
+    fn render_unified(&self, changeset: &Changeset, context: usize) -> Result<String, fmtError> {
+        let lines = lines(&changeset.diffs);
+        let mut t = String::new();
+
+        let mut i = 0;
+        while i < lines.len() {
+            // Find the next change and extend the hunk as long as changes are within reach.
+            let first_change = match lines[i..].iter().position(|&(tag, _)| tag != Tag::Same) {
+                Some(offset) => i + offset,
+                None => break,
+            };
+            let start = first_change.saturating_sub(context);
+            let mut end = first_change;
+            let mut j = first_change;
+            while j < lines.len() {
+                if lines[j].0 != Tag::Same {
+                    end = j + 1;
+                } else if j - end >= 2 * context {
+                    break;
+                }
+                j += 1;
+            }
+            let end = (end + context).min(lines.len());
+
+            let old_before = lines[..start].iter().filter(|l| l.0 != Tag::Add).count();
+            let new_before = lines[..start].iter().filter(|l| l.0 != Tag::Rem).count();
+            let old_len = lines[start..end].iter().filter(|l| l.0 != Tag::Add).count();
+            let new_len = lines[start..end].iter().filter(|l| l.0 != Tag::Rem).count();
+            let header = format!(
+                "@@ -{},{} +{},{} @@",
+                hunk_start(old_before, old_len),
+                old_len,
+                hunk_start(new_before, new_len),
+                new_len
+            );
+            writeln!(t, "{}", self.palette.cyan(&header))?;
+
+            for &(tag, line) in &lines[start..end] {
+                match tag {
+                    Tag::Same => writeln!(t, " {}", line)?,
+                    Tag::Rem => writeln!(t, "{}", self.removed("-", line))?,
+                    Tag::Add => writeln!(t, "{}", self.added("+", line))?,
+                }
+            }
+
+            i = end;
+        }
+
+        Ok(t)
+    }
+
+    fn render_side_by_side(&self, changeset: &Changeset, width: usize) -> Result<String, fmtError> {
+        let column = (width.saturating_sub(3) / 2).max(10);
+        let rows = elide(rows(&lines(&changeset.diffs)), ELIDE_CONTEXT);
+        let mut t = String::new();
+
+        for row in rows {
+            match row {
+                Row::Same(line) => {
+                    let line = fit(line, column);
+                    writeln!(t, "{}   {}", line, line)?;
+                }
+                Row::Changed(left, right) => {
+                    let marker = match (left, right) {
+                        (Some(_), Some(_)) => "|",
+                        (Some(_), None) => "<",
+                        _ => ">",
                     };
+                    let left = left.map_or(Cow::Borrowed(""), |l| self.visible(l));
+                    let right = right.map_or(Cow::Borrowed(""), |r| self.visible(r));
+                    let left = self.palette.red(&fit(&left, column));
+                    let right = self.palette.green(&fit(&right, column));
+                    writeln!(t, "{} {} {}", left, marker, right)?;
+                }
+                Row::Elided(count) => {
+                    writeln!(t, "{}", self.palette.dimmed(&elided_marker(count)))?;
+                }
+            }
+        }
+
+        Ok(t)
+    }
+
+    fn render_inline(&self, changeset: &Changeset) -> Result<String, fmtError> {
+        let rows = elide(rows(&lines(&changeset.diffs)), ELIDE_CONTEXT);
+        let mut t = String::new();
+
+        for row in rows {
+            match row {
+                Row::Same(line) => writeln!(t, " {}", line)?,
+                Row::Changed(Some(old), Some(new)) => {
+                    let words: Vec<String> = Changeset::new(old, new, " ")
+                        .diffs
+                        .iter()
+                        .map(|word| match *word {
+                            Difference::Same(ref z) => z.clone(),
+                            Difference::Rem(ref z) => {
+                                self.palette.red(&format!("[-{}-]", self.visible(z)))
+                            }
+                            Difference::Add(ref z) => {
+                                self.palette.green(&format!("{{+{}+}}", self.visible(z)))
+                            }
+                        })
+                        .collect();
+                    writeln!(t, "~{}", words.join(" "))?;
+                }
+                Row::Changed(Some(old), None) => {
+                    let old = format!("[-{}-]", self.visible(old));
+                    writeln!(t, "{}", self.removed("-", &old))?;
+                }
+                Row::Changed(_, new) => {
+                    let new = format!("{{+{}+}}", self.visible(new.unwrap_or("")));
+                    writeln!(t, "{}", self.added("+", &new))?;
+                }
+                Row::Elided(count) => {
+                    writeln!(t, "{}", self.palette.dimmed(&elided_marker(count)))?;
                 }
             }
         }
+
+        Ok(t)
     }
+}
+
+/// Make trailing spaces and tabs visible.
+fn visible_whitespace(line: &str) -> String {
+    let content = line.trim_end_matches(&[' ', '\t'][..]);
+    let trailing: String = line[content.len()..]
+        .chars()
+        .map(|c| if c == '\t' { '→' } else { '·' })
+        .collect();
+    format!("{}{}", content, trailing)
+}
+
+fn terminal_width() -> Option<usize> {
+    env::var("COLUMNS").ok().and_then(|c| c.trim().parse().ok())
+}
 
-    Ok(t)
+fn lines(diffs: &[Difference]) -> Vec<(Tag, &str)> {
+    let mut lines = vec![];
+    for diff in diffs {
+        let (tag, text) = match *diff {
+            Difference::Same(ref x) => (Tag::Same, x),
+            Difference::Rem(ref x) => (Tag::Rem, x),
+            Difference::Add(ref x) => (Tag::Add, x),
+        };
+        lines.extend(text.split('\n').map(|line| (tag, line)));
+    }
+    lines
+}
+
+/// Pair up removed and added lines so they can be shown next to each other.
+fn rows<'a>(lines: &[(Tag, &'a str)]) -> Vec<Row<'a>> {
+    let mut rows = vec![];
+    let mut i = 0;
+    while i < lines.len() {
+        if lines[i].0 == Tag::Same {
+            rows.push(Row::Same(lines[i].1));
+            i += 1;
+            continue;
+        }
+
+        let mut removed = vec![];
+        while i < lines.len() && lines[i].0 == Tag::Rem {
+            removed.push(lines[i].1);
+            i += 1;
+        }
+        let mut added = vec![];
+        while i < lines.len() && lines[i].0 == Tag::Add {
+            added.push(lines[i].1);
+            i += 1;
+        }
+        for j in 0..removed.len().max(added.len()) {
+            rows.push(Row::Changed(removed.get(j).cloned(), added.get(j).cloned()));
+        }
+    }
+    rows
+}
+
+/// Replace unchanged runs longer than `context` lines around each change with a marker.
+fn elide(rows: Vec<Row>, context: usize) -> Vec<Row> {
+    let mut result = vec![];
+    let mut i = 0;
+    while i < rows.len() {
+        if !is_same(&rows[i]) {
+            result.push(rows[i]);
+            i += 1;
+            continue;
+        }
+
+        let start = i;
+        while i < rows.len() && is_same(&rows[i]) {
+            i += 1;
+        }
+        let head = if start == 0 { 0 } else { context };
+        let tail = if i == rows.len() { 0 } else { context };
+        if i - start > head + tail + 1 {
+            result.extend_from_slice(&rows[start..start + head]);
+            result.push(Row::Elided(i - start - head - tail));
+            result.extend_from_slice(&rows[i - tail..i]);
+        } else {
+            result.extend_from_slice(&rows[start..i]);
+        }
+    }
+    result
+}
+
+fn is_same(row: &Row) -> bool {
+    matches!(*row, Row::Same(_))
+}
+
+fn elided_marker(count: usize) -> String {
+    format!(
+        "... {} unchanged line{} ...",
+        count,
+        if count == 1 { "" } else { "s" }
+    )
+}
+
+/// Keep at most `context` lines at the edges of an unchanged region that borders a change.
+fn elide_same(text: &str, first: bool, last: bool, palette: &Palette) -> String {
+    let lines: Vec<_> = text.split('\n').collect();
+    let head = if first { 0 } else { ELIDE_CONTEXT };
+    let tail = if last { 0 } else { ELIDE_CONTEXT };
+    if lines.len() <= head + tail + 1 {
+        return text.to_owned();
+    }
+
+    let mut kept: Vec<String> = lines[..head].iter().map(|l| l.to_string()).collect();
+    kept.push(palette.dimmed(&elided_marker(lines.len() - head - tail)));
+    kept.extend(lines[lines.len() - tail..].iter().map(|l| l.to_string()));
+    kept.join("\n")
+}
+
+fn printable(byte: u8) -> char {
+    if byte.is_ascii_graphic() || byte == b' ' {
+        byte as char
+    } else {
+        '.'
+    }
+}
+
+/// Mark the bytes that differ between two rows, aligned with `hex_row`.
+fn hex_carets(left: &[u8], right: &[u8]) -> String {
+    let mut carets = " ".repeat(10);
+    for i in 0..HEX_ROW {
+        if i == HEX_ROW / 2 {
+            carets.push(' ');
+        }
+        if left.get(i) != right.get(i) {
+            carets.push_str("^^ ");
+        } else {
+            carets.push_str("   ");
+        }
+    }
+    carets.trim_end().to_owned()
+}
+
+/// Line number a hunk starts at; empty ranges refer to the line before them.
+fn hunk_start(before: usize, len: usize) -> usize {
+    if len == 0 {
+        before
+    } else {
+        before + 1
+    }
+}
+
+/// Truncate or pad `line` to exactly `width` characters.
+fn fit(line: &str, width: usize) -> String {
+    let len = line.chars().count();
+    if len > width {
+        let mut fitted: String = line.chars().take(width - 1).collect();
+        fitted.push('…');
+        fitted
+    } else {
+        format!("{}{}", line, " ".repeat(width - len))
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    fn render(changeset: &Changeset) -> Result<String, fmtError> {
+        Renderer::new(DiffStyle::Full, ColorChoice::Always).render(changeset)
+    }
+
+    fn render_plain(changeset: &Changeset, style: DiffStyle) -> String {
+        Renderer::new(style, ColorChoice::Never)
+            .render(changeset)
+            .unwrap()
+    }
+
     #[test]
     fn basic_diff() {
         let diff = Changeset::new("lol", "yay", "\n");
@@ -102,4 +618,141 @@ ullamco laboris nisi ut aliquip ex ea commodo consequat.",
             "\u{1b}[32m+Line 0\u{1b}[0m\n Line 1\nLine 2\nLine 3\n"
         );
     }
+
+    fn numbers(range: ::std::ops::Range<usize>) -> String {
+        range.map(|i| i.to_string()).collect::<Vec<_>>().join("\n")
+    }
+
+    #[test]
+    fn full_elides_long_unchanged_regions() {
+        let expected = numbers(1..21);
+        let got = expected.replace("\n10\n", "\nten\n");
+        let diff = Changeset::new(&expected, &got, "\n");
+        assert_eq!(
+            render_plain(&diff, DiffStyle::Full),
+            " ... 6 unchanged lines ...\n7\n8\n9\n-10\n+{+ten+} \n 11\n12\n13\n... 7 unchanged lines ...\n"
+        );
+    }
+
+    #[test]
+    fn unified_diff() {
+        let expected = numbers(1..21);
+        let got = expected
+            .replace("\n3\n", "\nthree\n")
+            .replace("\n18\n", "\n");
+        let diff = Changeset::new(&expected, &got, "\n");
+        assert_eq!(
+            render_plain(&diff, DiffStyle::Unified { context: 1 }),
+            "@@ -2,3 +2,3 @@\n 2\n-3\n+three\n 4\n@@ -17,3 +17,2 @@\n 17\n-18\n 19\n"
+        );
+    }
+
+    #[test]
+    fn unified_diff_merges_close_hunks() {
+        let diff = Changeset::new("a\nb\nc\nd", "A\nb\nc\nD", "\n");
+        assert_eq!(
+            render_plain(&diff, DiffStyle::Unified { context: 1 }),
+            "@@ -1,4 +1,4 @@\n-a\n+A\n b\n c\n-d\n+D\n"
+        );
+    }
+
+    #[test]
+    fn unified_diff_colored() {
+        let diff = Changeset::new("a", "b", "\n");
+        let renderer = Renderer::new(DiffStyle::Unified { context: 1 }, ColorChoice::Always);
+        assert_eq!(
+            renderer.render(&diff).unwrap(),
+            "\u{1b}[36m@@ -1,1 +1,1 @@\u{1b}[0m\n\u{1b}[31m-a\u{1b}[0m\n\u{1b}[32m+b\u{1b}[0m\n"
+        );
+    }
+
+    #[test]
+    fn side_by_side_diff() {
+        let diff = Changeset::new("same\nold\ngone", "same\nnew", "\n");
+        assert_eq!(
+            render_plain(&diff, DiffStyle::SideBySide { width: Some(23) }),
+            "same         same      \n\
+             old        | new       \n\
+             gone       <           \n"
+        );
+    }
+
+    #[test]
+    fn side_by_side_truncates_long_lines() {
+        let diff = Changeset::new("abcdefghijklmnop", "abcdefghijklmnoq", "\n");
+        assert_eq!(
+            render_plain(&diff, DiffStyle::SideBySide { width: Some(23) }),
+            "abcdefghi… | abcdefghi…\n"
+        );
+    }
+
+    #[test]
+    fn inline_diff() {
+        let diff = Changeset::new("keep\nthe quick fox\nold", "keep\nthe slow fox", "\n");
+        assert_eq!(
+            render_plain(&diff, DiffStyle::Inline),
+            " keep\n~the [-quick-] {+slow+} fox\n-[-old-]\n"
+        );
+    }
+
+    #[test]
+    fn plain_diff_shows_whitespace() {
+        let diff = Changeset::new("a \nb\tc", "a\nb\tc\t", "\n");
+        assert_eq!(
+            render_plain(&diff, DiffStyle::Unified { context: 0 }),
+            "@@ -1,2 +1,2 @@\n-a·\n-b\tc\n+a\n+b\tc→\n"
+        );
+    }
+
+    #[test]
+    fn text_diff_marks_missing_newline() {
+        let renderer = Renderer::new(DiffStyle::Unified { context: 1 }, ColorChoice::Never);
+        assert_eq!(
+            renderer.render_text("a\nb\n", "a\nb").unwrap(),
+            "@@ -2,1 +2,2 @@\n b\n+\\ No newline at end of file\n"
+        );
+        assert_eq!(
+            renderer.render_text("a", "b\n").unwrap(),
+            "@@ -1,2 +1,1 @@\n-a\n-\\ No newline at end of file\n+b\n"
+        );
+        assert_eq!(
+            renderer.render_text("a\n\nb", "a\nb").unwrap(),
+            "@@ -1,3 +1,2 @@\n a\n-\n b\n"
+        );
+    }
+
+    #[test]
+    fn hexdump_diff() {
+        let expected: Vec<u8> = (0..100).collect();
+        let mut got = expected.clone();
+        got[70] = b'A';
+        got.push(0xff);
+        let renderer = Renderer::new(DiffStyle::Full, ColorChoice::Never);
+        assert_eq!(
+            renderer.render_bytes(&expected, &got).unwrap(),
+            "expected 100 bytes, got 101 bytes, first difference at offset 0x00000046\n\
+             \x2000000000  00 01 02 03 04 05 06 07  08 09 0a 0b 0c 0d 0e 0f  |................|\n\
+             \x2000000010  10 11 12 13 14 15 16 17  18 19 1a 1b 1c 1d 1e 1f  |................|\n\
+             \x2000000020  20 21 22 23 24 25 26 27  28 29 2a 2b 2c 2d 2e 2f  | !\"#$%&'()*+,-./|\n\
+             \x2000000030  30 31 32 33 34 35 36 37  38 39 3a 3b 3c 3d 3e 3f  |0123456789:;<=>?|\n\
+             -00000040  40 41 42 43 44 45 46 47  48 49 4a 4b 4c 4d 4e 4f  |@ABCDEFGHIJKLMNO|\n\
+             +00000040  40 41 42 43 44 45 41 47  48 49 4a 4b 4c 4d 4e 4f  |@ABCDEAGHIJKLMNO|\n\
+             \x20                            ^^\n\
+             \x2000000050  50 51 52 53 54 55 56 57  58 59 5a 5b 5c 5d 5e 5f  |PQRSTUVWXYZ[\\]^_|\n\
+             -00000060  60 61 62 63                                       |`abc|\n\
+             +00000060  60 61 62 63 ff                                    |`abc.|\n\
+             \x20                      ^^\n"
+        );
+    }
+
+    #[test]
+    fn hexdump_elides_identical_rows() {
+        let expected = vec![0u8; 16 * 20];
+        let mut got = expected.clone();
+        got[16 * 10] = 1;
+        let renderer = Renderer::new(DiffStyle::Full, ColorChoice::Never);
+        let dump = renderer.render_bytes(&expected, &got).unwrap();
+        assert!(dump.contains("... 7 unchanged lines ...\n 00000070"));
+        assert!(dump.ends_with(" 000000d0  00 00 00 00 00 00 00 00  00 00 00 00 00 00 00 00  |................|\n... 6 unchanged lines ...\n"));
+    }
 }
@@ -1,9 +1,15 @@
 use std::ffi;
 use std::fmt;
 use std::io;
+use std::path::PathBuf;
+use std::time::Duration;
 
 use failure;
 
+use exit_code::{ExitCodeNames, ExpectedCode};
+use invocation::Invocation;
+use rusage::ResourceUsage;
+
 fn format_cmd(cmd: &[ffi::OsString]) -> String {
     let result: Vec<String> = cmd.iter()
         .map(|s| s.to_string_lossy().into_owned())
@@ -66,12 +72,53 @@ impl<T> ResultChainExt<T> for Result<T, io::Error> {
 #[derive(Debug)]
 pub struct AssertionError {
     cmd: Vec<ffi::OsString>,
+    elapsed: Option<Duration>,
+    invocation: Option<Box<Invocation>>,
+    previous_attempts: Vec<AssertionError>,
     cause: Option<failure::Error>,
 }
 
 impl AssertionError {
     pub(crate) fn new(cmd: Vec<ffi::OsString>) -> Self {
-        Self { cmd, cause: None }
+        Self {
+            cmd,
+            elapsed: None,
+            invocation: None,
+            previous_attempts: vec![],
+            cause: None,
+        }
+    }
+
+    pub(crate) fn with_elapsed(mut self, elapsed: Duration) -> Self {
+        self.elapsed = Some(elapsed);
+        self
+    }
+
+    /// Wall-clock time the command ran for, if it could be started.
+    pub fn elapsed(&self) -> Option<Duration> {
+        self.elapsed
+    }
+
+    pub(crate) fn with_invocation(mut self, invocation: Invocation) -> Self {
+        self.invocation = Some(Box::new(invocation));
+        self
+    }
+
+    pub(crate) fn with_previous_attempts(mut self, attempts: Vec<AssertionError>) -> Self {
+        self.previous_attempts = attempts;
+        self
+    }
+
+    /// Failures of the attempts before this one, see
+    /// [`Assert::retry`](struct.Assert.html#method.retry).
+    pub fn previous_attempts(&self) -> &[AssertionError] {
+        &self.previous_attempts
+    }
+
+    /// How the command was started, when requested with
+    /// [`Assert::show_invocation`](struct.Assert.html#method.show_invocation).
+    pub fn invocation(&self) -> Option<&Invocation> {
+        self.invocation.as_deref()
     }
 }
 
@@ -97,7 +144,28 @@ impl ChainFail for AssertionError {
 
 impl fmt::Display for AssertionError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "Assertion failed for `{}`", format_cmd(&self.cmd))
+        if self.cmd.is_empty() {
+            write!(f, "Assertion failed")?;
+        } else {
+            write!(f, "Assertion failed for `{}`", format_cmd(&self.cmd))?;
+        }
+        if let Some(elapsed) = self.elapsed {
+            write!(f, " after {:?}", elapsed)?;
+        }
+        if let Some(ref invocation) = self.invocation {
+            write!(f, "\n{}", invocation)?;
+        }
+        for (i, attempt) in self.previous_attempts.iter().enumerate() {
+            write!(f, "\nattempt {} failed: ", i + 1)?;
+            let mut chain = (attempt as &dyn failure::Fail).iter_chain();
+            if let Some(first) = chain.next() {
+                write!(f, "{}", first)?;
+            }
+            for cause in chain {
+                write!(f, "\n  with: {}", cause)?;
+            }
+        }
+        Ok(())
     }
 }
 
@@ -160,7 +228,8 @@ impl fmt::Display for StatusError {
 
 #[derive(Debug)]
 pub struct ExitCodeError {
-    expected: Option<i32>,
+    expected: ExpectedCode,
+    names: ExitCodeNames,
     got: Option<i32>,
     stdout: Vec<u8>,
     stderr: Vec<u8>,
@@ -168,9 +237,16 @@ pub struct ExitCodeError {
 }
 
 impl ExitCodeError {
-    pub fn new(expected: Option<i32>, got: Option<i32>, stdout: Vec<u8>, stderr: Vec<u8>) -> Self {
+    pub fn new(
+        expected: ExpectedCode,
+        names: ExitCodeNames,
+        got: Option<i32>,
+        stdout: Vec<u8>,
+        stderr: Vec<u8>,
+    ) -> Self {
         Self {
             expected,
+            names,
             got,
             stdout,
             stderr,
@@ -203,9 +279,371 @@ impl fmt::Display for ExitCodeError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         let out = String::from_utf8_lossy(&self.stdout);
         let err = String::from_utf8_lossy(&self.stderr);
-        writeln!(f, "expected={:?}", self.expected)?;
-        writeln!(f, "got={:?}", self.got)?;
+        writeln!(f, "expected={}", self.expected.describe(&self.names))?;
+        match self.got {
+            Some(code) => writeln!(f, "got={}", self.names.describe(code))?,
+            None => writeln!(f, "got=none, killed by a signal")?,
+        }
+        writeln!(f, "stdout=```{}```", out)?;
+        write!(f, "stderr=```{}```", err)
+    }
+}
+
+#[derive(Debug)]
+pub struct TerminatedError {
+    stdout: Vec<u8>,
+    stderr: Vec<u8>,
+    cause: Option<failure::Error>,
+}
+
+impl TerminatedError {
+    pub fn new(stdout: Vec<u8>, stderr: Vec<u8>) -> Self {
+        Self {
+            stdout,
+            stderr,
+            cause: None,
+        }
+    }
+}
+
+impl failure::Fail for TerminatedError {
+    fn cause(&self) -> Option<&dyn failure::Fail> {
+        self.cause.as_ref().map(failure::Error::as_fail)
+    }
+
+    fn backtrace(&self) -> Option<&failure::Backtrace> {
+        None
+    }
+}
+
+impl ChainFail for TerminatedError {
+    fn chain<E>(mut self, error: E) -> Self
+    where
+        E: Into<failure::Error>,
+    {
+        self.cause = Some(error.into());
+        self
+    }
+}
+
+impl fmt::Display for TerminatedError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let out = String::from_utf8_lossy(&self.stdout);
+        let err = String::from_utf8_lossy(&self.stderr);
+        writeln!(f, "Terminated the command early")?;
+        writeln!(f, "stdout so far=```{}```", out)?;
+        write!(f, "stderr so far=```{}```", err)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum Bound {
+    AtMost(Duration),
+    AtLeast(Duration),
+}
+
+#[derive(Debug)]
+pub struct TimingError {
+    expected: Bound,
+    elapsed: Duration,
+    terminated: bool,
+    stdout: Vec<u8>,
+    stderr: Vec<u8>,
+    cause: Option<failure::Error>,
+}
+
+impl TimingError {
+    pub fn new(
+        expected: Bound,
+        elapsed: Duration,
+        terminated: bool,
+        stdout: Vec<u8>,
+        stderr: Vec<u8>,
+    ) -> Self {
+        Self {
+            expected,
+            elapsed,
+            terminated,
+            stdout,
+            stderr,
+            cause: None,
+        }
+    }
+}
+
+impl failure::Fail for TimingError {
+    fn cause(&self) -> Option<&dyn failure::Fail> {
+        self.cause.as_ref().map(failure::Error::as_fail)
+    }
+
+    fn backtrace(&self) -> Option<&failure::Backtrace> {
+        None
+    }
+}
+
+impl ChainFail for TimingError {
+    fn chain<E>(mut self, error: E) -> Self
+    where
+        E: Into<failure::Error>,
+    {
+        self.cause = Some(error.into());
+        self
+    }
+}
+
+impl fmt::Display for TimingError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let out = String::from_utf8_lossy(&self.stdout);
+        let err = String::from_utf8_lossy(&self.stderr);
+        match self.expected {
+            Bound::AtMost(limit) => write!(f, "Expected to complete within {:?}", limit)?,
+            Bound::AtLeast(limit) => write!(f, "Expected to take at least {:?}", limit)?,
+        }
+        if self.terminated {
+            writeln!(f, ", was terminated after {:?}", self.elapsed)?;
+        } else {
+            writeln!(f, ", took {:?}", self.elapsed)?;
+        }
         writeln!(f, "stdout=```{}```", out)?;
         write!(f, "stderr=```{}```", err)
     }
 }
+
+#[derive(Debug, Clone, Copy)]
+pub enum Resource {
+    MaxRss(u64),
+    CpuTime(Duration),
+}
+
+#[derive(Debug)]
+pub struct ResourceError {
+    limit: Resource,
+    usage: Option<ResourceUsage>,
+    cause: Option<failure::Error>,
+}
+
+impl ResourceError {
+    pub fn new(limit: Resource, usage: Option<ResourceUsage>) -> Self {
+        Self {
+            limit,
+            usage,
+            cause: None,
+        }
+    }
+}
+
+impl failure::Fail for ResourceError {
+    fn cause(&self) -> Option<&dyn failure::Fail> {
+        self.cause.as_ref().map(failure::Error::as_fail)
+    }
+
+    fn backtrace(&self) -> Option<&failure::Backtrace> {
+        None
+    }
+}
+
+impl ChainFail for ResourceError {
+    fn chain<E>(mut self, error: E) -> Self
+    where
+        E: Into<failure::Error>,
+    {
+        self.cause = Some(error.into());
+        self
+    }
+}
+
+impl fmt::Display for ResourceError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.limit {
+            Resource::MaxRss(limit) => write!(f, "Expected peak RSS below {} bytes", limit)?,
+            Resource::CpuTime(limit) => write!(f, "Expected CPU time below {:?}", limit)?,
+        }
+        let usage = match self.usage {
+            Some(usage) => usage,
+            None => return write!(f, ", but resource usage isn't available on this platform"),
+        };
+        match self.limit {
+            Resource::MaxRss(_) => writeln!(f, ", got {} bytes", usage.max_rss())?,
+            Resource::CpuTime(_) => writeln!(f, ", got {:?}", usage.cpu_time())?,
+        }
+        write!(
+            f,
+            "usage: max_rss={} user={:?} sys={:?} minor_faults={} major_faults={}",
+            usage.max_rss(),
+            usage.user_time(),
+            usage.system_time(),
+            usage.minor_faults(),
+            usage.major_faults()
+        )
+    }
+}
+
+/// How an assertion fared in each run of a flakiness check.
+#[derive(Debug, Clone)]
+pub struct Variation {
+    pub assertion: String,
+    pub held: Vec<usize>,
+    pub failed: Vec<usize>,
+}
+
+#[derive(Debug)]
+pub struct FlakyError {
+    runs: usize,
+    varied: Vec<Variation>,
+    cause: Option<failure::Error>,
+}
+
+impl FlakyError {
+    pub fn new(runs: usize, varied: Vec<Variation>) -> Self {
+        Self {
+            runs,
+            varied,
+            cause: None,
+        }
+    }
+}
+
+impl failure::Fail for FlakyError {
+    fn cause(&self) -> Option<&dyn failure::Fail> {
+        self.cause.as_ref().map(failure::Error::as_fail)
+    }
+
+    fn backtrace(&self) -> Option<&failure::Backtrace> {
+        None
+    }
+}
+
+impl ChainFail for FlakyError {
+    fn chain<E>(mut self, error: E) -> Self
+    where
+        E: Into<failure::Error>,
+    {
+        self.cause = Some(error.into());
+        self
+    }
+}
+
+impl fmt::Display for FlakyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let runs = |runs: &[usize]| {
+            let runs: Vec<_> = runs.iter().map(|run| run.to_string()).collect();
+            runs.join(", ")
+        };
+        write!(f, "Outcomes differed across {} runs:", self.runs)?;
+        for variation in &self.varied {
+            write!(
+                f,
+                "\n- {}: held in runs {}, failed in runs {}",
+                variation.assertion,
+                runs(&variation.held),
+                runs(&variation.failed)
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+pub struct DeterminismError {
+    run: usize,
+    differences: Vec<String>,
+    cause: Option<failure::Error>,
+}
+
+impl DeterminismError {
+    pub fn new(run: usize, differences: Vec<String>) -> Self {
+        Self {
+            run,
+            differences,
+            cause: None,
+        }
+    }
+}
+
+impl failure::Fail for DeterminismError {
+    fn cause(&self) -> Option<&dyn failure::Fail> {
+        self.cause.as_ref().map(failure::Error::as_fail)
+    }
+
+    fn backtrace(&self) -> Option<&failure::Backtrace> {
+        None
+    }
+}
+
+impl ChainFail for DeterminismError {
+    fn chain<E>(mut self, error: E) -> Self
+    where
+        E: Into<failure::Error>,
+    {
+        self.cause = Some(error.into());
+        self
+    }
+}
+
+impl fmt::Display for DeterminismError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Run {} differed from run 1:", self.run)?;
+        for difference in &self.differences {
+            write!(f, "\n{}", difference)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+pub struct DirError {
+    actual: PathBuf,
+    expected: PathBuf,
+    differences: Vec<String>,
+    cause: Option<failure::Error>,
+}
+
+impl DirError {
+    pub fn new(actual: PathBuf, expected: PathBuf, differences: Vec<String>) -> Self {
+        Self {
+            actual,
+            expected,
+            differences,
+            cause: None,
+        }
+    }
+}
+
+impl failure::Fail for DirError {
+    fn cause(&self) -> Option<&dyn failure::Fail> {
+        self.cause.as_ref().map(failure::Error::as_fail)
+    }
+
+    fn backtrace(&self) -> Option<&failure::Backtrace> {
+        None
+    }
+}
+
+impl ChainFail for DirError {
+    fn chain<E>(mut self, error: E) -> Self
+    where
+        E: Into<failure::Error>,
+    {
+        self.cause = Some(error.into());
+        self
+    }
+}
+
+impl fmt::Display for DirError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Directory `{}` doesn't match `{}`",
+            self.actual.display(),
+            self.expected.display()
+        )?;
+        if self.differences.is_empty() {
+            return Ok(());
+        }
+        write!(f, ":")?;
+        for difference in &self.differences {
+            write!(f, "\n{}", difference)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,208 @@
+use std::collections::BTreeMap;
+use std::ops::{Bound, RangeBounds};
+
+use failure;
+
+/// Names for exit codes, to expect codes by name and to show names next to numbers in failures.
+///
+/// Starts out with the codes of `sysexits.h`, see [`empty`](#method.empty) for a table without
+/// them.
+///
+/// # Examples
+///
+/// ```rust
+/// extern crate assert_cli;
+/// use assert_cli::{Assert, ExitCodeNames};
+///
+/// Assert::command(&["sh", "-c", "exit 3"])
+///     .exit_code_names(ExitCodeNames::sysexits().name(3, "EX_CONFLICT"))
+///     .fails_with_named("EX_CONFLICT")
+///     .unwrap();
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExitCodeNames {
+    names: BTreeMap<i32, String>,
+}
+
+impl ExitCodeNames {
+    /// Create a table with the codes of `sysexits.h`, like `default` and
+    /// [`sysexits`](#method.sysexits).
+    pub fn new() -> Self {
+        Self::sysexits()
+    }
+
+    /// Create a table without any names.
+    pub fn empty() -> Self {
+        Self {
+            names: BTreeMap::new(),
+        }
+    }
+
+    /// Create a table with the codes of `sysexits.h`, like `EX_USAGE` for 64.
+    pub fn sysexits() -> Self {
+        let sysexits = [
+            (0, "EX_OK"),
+            (64, "EX_USAGE"),
+            (65, "EX_DATAERR"),
+            (66, "EX_NOINPUT"),
+            (67, "EX_NOUSER"),
+            (68, "EX_NOHOST"),
+            (69, "EX_UNAVAILABLE"),
+            (70, "EX_SOFTWARE"),
+            (71, "EX_OSERR"),
+            (72, "EX_OSFILE"),
+            (73, "EX_CANTCREAT"),
+            (74, "EX_IOERR"),
+            (75, "EX_TEMPFAIL"),
+            (76, "EX_PROTOCOL"),
+            (77, "EX_NOPERM"),
+            (78, "EX_CONFIG"),
+        ];
+        sysexits
+            .iter()
+            .fold(Self::empty(), |names, &(code, name)| names.name(code, name))
+    }
+
+    /// Name `code`, replacing its previous name.
+    pub fn name<N: Into<String>>(mut self, code: i32, name: N) -> Self {
+        self.names.insert(code, name.into());
+        self
+    }
+
+    /// The code named `name`.
+    pub fn code(&self, name: &str) -> Option<i32> {
+        self.names
+            .iter()
+            .find(|&(_, n)| n == name)
+            .map(|(&code, _)| code)
+    }
+
+    /// The name of `code`.
+    pub fn name_of(&self, code: i32) -> Option<&str> {
+        self.names.get(&code).map(String::as_str)
+    }
+
+    /// `code` with its name, like `64 (EX_USAGE)`.
+    pub(crate) fn describe(&self, code: i32) -> String {
+        match self.name_of(code) {
+            Some(name) => format!("{} ({})", code, name),
+            None => code.to_string(),
+        }
+    }
+}
+
+impl Default for ExitCodeNames {
+    fn default() -> Self {
+        Self::sysexits()
+    }
+}
+
+/// The exit codes a command is expected to exit with.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum ExpectedCode {
+    Exactly(i32),
+    /// Looked up in the `ExitCodeNames` before the command runs.
+    Named(String),
+    /// Inclusive on both ends.
+    Range(i32, i32),
+    OneOf(Vec<i32>),
+    Not(i32),
+}
+
+impl ExpectedCode {
+    pub fn range<R: RangeBounds<i32>>(range: &R) -> Self {
+        let low = match range.start_bound() {
+            Bound::Included(&low) => low,
+            Bound::Excluded(&low) => low.saturating_add(1),
+            Bound::Unbounded => i32::MIN,
+        };
+        let high = match range.end_bound() {
+            Bound::Included(&high) => high,
+            Bound::Excluded(&high) => high.saturating_sub(1),
+            Bound::Unbounded => i32::MAX,
+        };
+        ExpectedCode::Range(low, high)
+    }
+
+    /// Look up a named code.
+    pub fn resolve(&self, names: &ExitCodeNames) -> Result<Self, failure::Error> {
+        match *self {
+            ExpectedCode::Named(ref name) => match names.code(name) {
+                Some(code) => Ok(ExpectedCode::Exactly(code)),
+                None => Err(failure::err_msg(format!(
+                    "No exit code is named `{}`",
+                    name
+                ))),
+            },
+            ref expected => Ok(expected.clone()),
+        }
+    }
+
+    /// Describe the codes, with names from `names`.
+    pub fn describe(&self, names: &ExitCodeNames) -> String {
+        match *self {
+            ExpectedCode::Exactly(code) => names.describe(code),
+            ExpectedCode::Named(ref name) => name.clone(),
+            ExpectedCode::Range(low, i32::MAX) => format!("{}..", low),
+            ExpectedCode::Range(i32::MIN, high) => format!("..={}", high),
+            ExpectedCode::Range(low, high) => format!("{}..={}", low, high),
+            ExpectedCode::OneOf(ref codes) => {
+                let codes: Vec<_> = codes.iter().map(|&c| names.describe(c)).collect();
+                format!("one of [{}]", codes.join(", "))
+            }
+            ExpectedCode::Not(code) => format!("anything but {}", names.describe(code)),
+        }
+    }
+
+    /// Whether `code` is accepted, a command killed by a signal never is.
+    ///
+    /// Named codes need to be resolved first.
+    pub fn accepts(&self, code: Option<i32>) -> bool {
+        let code = match code {
+            Some(code) => code,
+            None => return false,
+        };
+        match *self {
+            ExpectedCode::Exactly(expected) => code == expected,
+            ExpectedCode::Named(_) => false,
+            ExpectedCode::Range(low, high) => low <= code && code <= high,
+            ExpectedCode::OneOf(ref codes) => codes.contains(&code),
+            ExpectedCode::Not(rejected) => code != rejected,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn ranges() {
+        assert_eq!(ExpectedCode::range(&(2..10)), ExpectedCode::Range(2, 9));
+        let names = ExitCodeNames::empty();
+        assert_eq!(ExpectedCode::range(&(2..=9)).describe(&names), "2..=9");
+        assert_eq!(ExpectedCode::range(&(2..)).describe(&names), "2..");
+        assert!(ExpectedCode::range(&(..=1)).accepts(Some(-1)));
+        assert!(!ExpectedCode::range(&(2..10)).accepts(Some(10)));
+        assert!(!ExpectedCode::Not(1).accepts(None));
+    }
+
+    #[test]
+    fn names() {
+        let names = ExitCodeNames::sysexits().name(3, "EX_CONFLICT");
+        assert_eq!(names.code("EX_DATAERR"), Some(65));
+        assert_eq!(ExitCodeNames::new(), ExitCodeNames::default());
+        assert_eq!(ExitCodeNames::empty().code("EX_USAGE"), None);
+        assert_eq!(
+            ExpectedCode::OneOf(vec![1, 3, 64]).describe(&names),
+            "one of [1, 3 (EX_CONFLICT), 64 (EX_USAGE)]"
+        );
+        let named = ExpectedCode::Named("EX_USAGE".to_owned());
+        assert_eq!(named.resolve(&names).unwrap(), ExpectedCode::Exactly(64));
+        let unknown = ExpectedCode::Named("EX_NOPE".to_owned());
+        assert_eq!(
+            unknown.resolve(&names).unwrap_err().to_string(),
+            "No exit code is named `EX_NOPE`"
+        );
+    }
+}
@@ -0,0 +1,332 @@
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use determinism;
+use diff::Renderer;
+use errors::{ChainFail, DirError};
+
+/// Setting this environment variable overwrites expected directories with what was produced.
+pub(crate) const UPDATE_VAR: &str = "ASSERT_CLI_UPDATE";
+
+/// A directory the command produces, compared against an expected one.
+#[derive(Debug)]
+pub(crate) struct GoldenDir {
+    pub actual: PathBuf,
+    pub expected: PathBuf,
+    pub permissions: bool,
+    pub symlinks: bool,
+    /// Overwrite `expected` instead of comparing, see [`update_requested`].
+    pub update: bool,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Entry {
+    Dir { mode: u32 },
+    File { contents: Vec<u8>, mode: u32 },
+    Symlink(PathBuf),
+}
+
+impl Entry {
+    fn kind(&self) -> &'static str {
+        match *self {
+            Entry::Dir { .. } => "directory",
+            Entry::File { .. } => "file",
+            Entry::Symlink(_) => "symlink",
+        }
+    }
+}
+
+impl GoldenDir {
+    /// Compare the directories, with `actual` relative to `current_dir`.
+    pub fn verify(&self, current_dir: Option<&Path>, renderer: &Renderer) -> Result<(), DirError> {
+        let actual = match current_dir {
+            Some(dir) => dir.join(&self.actual),
+            None => self.actual.clone(),
+        };
+        let error = |differences| DirError::new(actual.clone(), self.expected.clone(), differences);
+
+        if self.update {
+            return overwrite(&actual, &self.expected).map_err(|e| error(vec![]).chain(e));
+        }
+
+        let got = self.read(&actual).map_err(|e| error(vec![]).chain(e))?;
+        let expected = self.read(&self.expected).map_err(|e| {
+            let e = if e.kind() == io::ErrorKind::NotFound {
+                let hint = format!("{}, run with {}=1 to create it", e, UPDATE_VAR);
+                io::Error::new(e.kind(), hint)
+            } else {
+                e
+            };
+            error(vec![]).chain(e)
+        })?;
+        let differences = self.differences(&expected, &got, renderer);
+        if differences.is_empty() {
+            Ok(())
+        } else {
+            Err(error(differences))
+        }
+    }
+
+    fn read(&self, root: &Path) -> io::Result<BTreeMap<PathBuf, Entry>> {
+        let mut entries = BTreeMap::new();
+        self.read_into(root, Path::new(""), &mut entries)?;
+        Ok(entries)
+    }
+
+    fn read_into(
+        &self,
+        dir: &Path,
+        prefix: &Path,
+        entries: &mut BTreeMap<PathBuf, Entry>,
+    ) -> io::Result<()> {
+        for entry in fs::read_dir(dir)? {
+            let entry = entry?;
+            let path = prefix.join(entry.file_name());
+            let metadata = if self.symlinks {
+                fs::symlink_metadata(entry.path())?
+            } else {
+                fs::metadata(entry.path())?
+            };
+            if metadata.file_type().is_symlink() {
+                entries.insert(path, Entry::Symlink(fs::read_link(entry.path())?));
+            } else if metadata.is_dir() {
+                let mode = mode(&metadata);
+                self.read_into(&entry.path(), &path, entries)?;
+                entries.insert(path, Entry::Dir { mode });
+            } else {
+                let contents = fs::read(entry.path())?;
+                let mode = mode(&metadata);
+                entries.insert(path, Entry::File { contents, mode });
+            }
+        }
+        Ok(())
+    }
+
+    fn differences(
+        &self,
+        expected: &BTreeMap<PathBuf, Entry>,
+        got: &BTreeMap<PathBuf, Entry>,
+        renderer: &Renderer,
+    ) -> Vec<String> {
+        let mut differences = vec![];
+        for (path, expected) in expected {
+            let got = match got.get(path) {
+                Some(got) => got,
+                None => {
+                    differences.push(format!("{}: missing", path.display()));
+                    continue;
+                }
+            };
+            match (expected, got) {
+                (&Entry::Dir { mode: expected }, &Entry::Dir { mode: got })
+                    if self.permissions && expected != got =>
+                {
+                    differences.push(format_mode(path, expected, got))
+                }
+                (
+                    &Entry::File {
+                        contents: ref expected,
+                        mode: expected_mode,
+                    },
+                    &Entry::File {
+                        contents: ref got,
+                        mode: got_mode,
+                    },
+                ) => {
+                    if expected != got {
+                        differences.push(format!(
+                            "{}:\n{}",
+                            path.display(),
+                            determinism::render(expected, got, renderer)
+                        ));
+                    }
+                    if self.permissions && expected_mode != got_mode {
+                        differences.push(format_mode(path, expected_mode, got_mode));
+                    }
+                }
+                (Entry::Symlink(expected), Entry::Symlink(got)) if expected != got => differences
+                    .push(format!(
+                        "{}: links to `{}` instead of `{}`",
+                        path.display(),
+                        got.display(),
+                        expected.display()
+                    )),
+                (expected, got) if expected.kind() != got.kind() => differences.push(format!(
+                    "{}: is a {} instead of a {}",
+                    path.display(),
+                    got.kind(),
+                    expected.kind()
+                )),
+                _ => {}
+            }
+        }
+        for path in got.keys().filter(|p| !expected.contains_key(*p)) {
+            differences.push(format!("{}: unexpected", path.display()));
+        }
+        differences
+    }
+}
+
+fn format_mode(path: &Path, expected: u32, got: u32) -> String {
+    format!(
+        "{}: mode {:o} instead of {:o}",
+        path.display(),
+        got,
+        expected
+    )
+}
+
+/// Whether `UPDATE_VAR` asks to overwrite expected directories.
+pub(crate) fn update_requested() -> bool {
+    env::var_os(UPDATE_VAR).is_some_and(|v| !v.is_empty() && v != "0")
+}
+
+/// Replace `expected` by a copy of `actual`.
+fn overwrite(actual: &Path, expected: &Path) -> io::Result<()> {
+    // Fail before removing anything.
+    fs::read_dir(actual)?;
+    if expected.exists() {
+        fs::remove_dir_all(expected)?;
+    }
+    fs::create_dir_all(expected)?;
+    determinism::copy_dir(actual, expected)
+}
+
+#[cfg(unix)]
+fn mode(metadata: &fs::Metadata) -> u32 {
+    use std::os::unix::fs::PermissionsExt;
+    metadata.permissions().mode() & 0o7777
+}
+
+#[cfg(not(unix))]
+fn mode(metadata: &fs::Metadata) -> u32 {
+    if metadata.permissions().readonly() {
+        0o444
+    } else {
+        0o644
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use assert::Assert;
+    use color::ColorChoice;
+    use diff::DiffStyle;
+    use failure::Fail;
+    use tempfile;
+
+    fn write(root: &Path, files: &[(&str, &str)]) {
+        for &(path, contents) in files {
+            let path = root.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, contents).unwrap();
+        }
+    }
+
+    #[test]
+    fn reports_differences_and_updates() {
+        let work = tempfile::tempdir().unwrap();
+        let expected = work.path().join("expected");
+        write(
+            &expected,
+            &[("same", "x\n"), ("changed", "a\nb\n"), ("gone", "")],
+        );
+        let generate = || {
+            Assert::command(&[
+                "sh",
+                "-c",
+                "mkdir -p out; echo x > out/same; printf 'a\\nc\\n' > out/changed; touch out/new",
+            ])
+            .current_dir(work.path())
+            .dir("out")
+            .matches(&expected)
+        };
+        let golden = |update| GoldenDir {
+            actual: PathBuf::from("out"),
+            expected: expected.clone(),
+            permissions: false,
+            symlinks: false,
+            update,
+        };
+        let renderer = Renderer::new(DiffStyle::default(), ColorChoice::Never);
+
+        let err = generate().execute().unwrap_err();
+        let message = Assert::format_causes(err.causes());
+        assert!(message.contains("with: Directory `"), "{}", message);
+        assert!(message.contains("\nchanged:\n"), "{}", message);
+        assert!(
+            message.contains("\ngone: missing\nnew: unexpected"),
+            "{}",
+            message
+        );
+        assert!(!message.contains("\nsame"), "{}", message);
+
+        assert!(golden(false).verify(Some(work.path()), &renderer).is_err());
+        golden(true).verify(Some(work.path()), &renderer).unwrap();
+        assert_eq!(
+            fs::read_to_string(expected.join("changed")).unwrap(),
+            "a\nc\n"
+        );
+        generate().unwrap();
+    }
+
+    #[test]
+    fn marks_missing_final_newline() {
+        let work = tempfile::tempdir().unwrap();
+        let expected = work.path().join("expected");
+        write(&expected, &[("file", "x\n")]);
+        let err = Assert::command(&["sh", "-c", "mkdir -p out; printf x > out/file"])
+            .current_dir(work.path())
+            .dir("out")
+            .matches(&expected)
+            .execute()
+            .unwrap_err();
+        let message = Assert::format_causes(err.causes());
+        assert!(
+            message.contains("\nfile:\n x\n+\\ No newline at end of file\n"),
+            "{}",
+            message
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn compares_permissions_and_symlinks() {
+        use std::os::unix::fs::{symlink, PermissionsExt};
+
+        let work = tempfile::tempdir().unwrap();
+        let expected = work.path().join("expected");
+        write(&expected, &[("run.sh", ""), ("target", "")]);
+        symlink("target", expected.join("link")).unwrap();
+        let generate = || {
+            Assert::command(&["sh", "-c", "rm -rf out; mkdir out; touch out/run.sh out/target; chmod 755 out/run.sh; cp out/target out/link"])
+                .current_dir(work.path())
+        };
+        fs::set_permissions(expected.join("run.sh"), fs::Permissions::from_mode(0o644)).unwrap();
+
+        generate().dir("out").matches(&expected).unwrap();
+
+        let err = generate()
+            .dir("out")
+            .with_permissions()
+            .with_symlinks()
+            .matches(&expected)
+            .execute()
+            .unwrap_err();
+        let message = Assert::format_causes(err.causes());
+        assert!(
+            message.contains("\nlink: is a file instead of a symlink"),
+            "{}",
+            message
+        );
+        assert!(
+            message.contains("\nrun.sh: mode 755 instead of 644"),
+            "{}",
+            message
+        );
+    }
+}
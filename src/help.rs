@@ -0,0 +1,435 @@
+use std::fmt;
+
+/// Help text of a clap-style command, split into sections, flags and subcommands.
+///
+/// Understands the layouts of clap 2 (`USAGE:`, `FLAGS:`, `SUBCOMMANDS:`) as well as later
+/// ones (`Usage: ...`, `Options:`, `Commands:`), with descriptions next to or below entries.
+///
+/// # Examples
+///
+/// ```rust
+/// extern crate assert_cli;
+/// use assert_cli::Help;
+///
+/// let help = Help::parse("\
+/// Usage: mytool [OPTIONS] [COMMAND]
+///
+/// Commands:
+///   build, b  Compile the project
+///
+/// Options:
+///   -v, --verbose        Print more, repeat for even more
+///       --color <WHEN>   When to use colors
+/// ");
+/// assert_eq!(help.usage(), Some("mytool [OPTIONS] [COMMAND]"));
+/// assert!(help.flag("-v").unwrap().description().contains("repeat"));
+/// assert_eq!(help.flag("--color").unwrap().value(), Some("<WHEN>"));
+/// assert_eq!(help.subcommand("build").unwrap().aliases(), ["b"]);
+/// ```
+#[derive(Debug, Clone)]
+pub struct Help {
+    sections: Vec<Section>,
+    flags: Vec<Flag>,
+    pub(crate) subcommands: Vec<Subcommand>,
+}
+
+#[derive(Debug, Clone)]
+struct Section {
+    name: String,
+    body: String,
+}
+
+/// A flag or option listed in help text.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Flag {
+    short: Option<String>,
+    long: Option<String>,
+    value: Option<String>,
+    description: String,
+}
+
+impl Flag {
+    /// The short form, like `-v`.
+    pub fn short(&self) -> Option<&str> {
+        self.short.as_deref()
+    }
+
+    /// The long form, like `--verbose`.
+    pub fn long(&self) -> Option<&str> {
+        self.long.as_deref()
+    }
+
+    /// The value the flag takes, like `<FILE>`.
+    pub fn value(&self) -> Option<&str> {
+        self.value.as_deref()
+    }
+
+    /// The description, its lines joined by spaces.
+    pub fn description(&self) -> &str {
+        &self.description
+    }
+}
+
+impl fmt::Display for Flag {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let names: Vec<_> = self.short.iter().chain(self.long.iter()).collect();
+        for (i, name) in names.iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{}", name)?;
+        }
+        if let Some(ref value) = self.value {
+            write!(f, " {}", value)?;
+        }
+        Ok(())
+    }
+}
+
+/// A subcommand listed in help text.
+#[derive(Debug, Clone)]
+pub struct Subcommand {
+    name: String,
+    aliases: Vec<String>,
+    description: String,
+    pub(crate) help: Option<Box<Help>>,
+}
+
+impl Subcommand {
+    /// The name to invoke it with.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Other names listed for it, like `b` in `build, b`.
+    pub fn aliases(&self) -> &[String] {
+        &self.aliases
+    }
+
+    /// The description, its lines joined by spaces.
+    pub fn description(&self) -> &str {
+        &self.description
+    }
+
+    /// The subcommand's own help, when collected with
+    /// [`Assert::help_recursive`](struct.Assert.html#method.help_recursive).
+    pub fn help(&self) -> Option<&Help> {
+        self.help.as_deref()
+    }
+}
+
+/// A line of a section's entry list: a flag, subcommand or argument and its description.
+struct Entry {
+    term: String,
+    description: Vec<String>,
+}
+
+impl Help {
+    /// Parse help text.
+    ///
+    /// Unindented lines ending in `:`, or starting with a word and a `:` like `Usage: ...`,
+    /// start sections.  Their indented lines list entries, whose description follows after
+    /// two spaces or on deeper indented lines.
+    pub fn parse(text: &str) -> Self {
+        let mut sections = vec![];
+        let mut current: Option<(String, Option<&str>, Vec<&str>)> = None;
+        for line in text.lines() {
+            let line = line.trim_end();
+            let indented = line.starts_with(' ') || line.starts_with('\t');
+            if line.is_empty() || indented {
+                if let Some((_, _, ref mut lines)) = current {
+                    lines.push(line);
+                }
+                continue;
+            }
+            sections.extend(current.take());
+            current = header(line).map(|(name, inline)| (name.to_owned(), inline, vec![]));
+        }
+        sections.extend(current);
+
+        let mut help = Help {
+            sections: vec![],
+            flags: vec![],
+            subcommands: vec![],
+        };
+        for (name, inline, lines) in sections {
+            let listing = name.to_lowercase().ends_with("commands");
+            for entry in entries(&lines) {
+                if entry.term.starts_with('-') {
+                    help.flags.push(flag(&entry));
+                } else if listing {
+                    help.subcommands.extend(subcommand(&entry));
+                }
+            }
+            let mut body: Vec<_> = inline.into_iter().map(str::to_owned).collect();
+            body.extend(dedent(&lines));
+            help.sections.push(Section {
+                name,
+                body: body.join("\n").trim_end().to_owned(),
+            });
+        }
+        help
+    }
+
+    /// The names of the sections, in order.
+    pub fn section_names(&self) -> Vec<&str> {
+        self.sections.iter().map(|s| s.name.as_str()).collect()
+    }
+
+    /// The text of the section named `name`, ignoring case, without its indentation.
+    pub fn section(&self, name: &str) -> Option<&str> {
+        self.sections
+            .iter()
+            .find(|s| s.name.eq_ignore_ascii_case(name))
+            .map(|s| s.body.as_str())
+    }
+
+    /// The usage section.
+    pub fn usage(&self) -> Option<&str> {
+        self.section("usage")
+    }
+
+    /// The flags and options of all sections.
+    pub fn flags(&self) -> &[Flag] {
+        &self.flags
+    }
+
+    /// The flag whose short or long form is `name`, like `-v` or `--verbose`.
+    pub fn flag(&self, name: &str) -> Option<&Flag> {
+        self.flags
+            .iter()
+            .find(|f| f.short() == Some(name) || f.long() == Some(name))
+    }
+
+    /// The subcommands of sections named like `SUBCOMMANDS` or `Commands`.
+    pub fn subcommands(&self) -> &[Subcommand] {
+        &self.subcommands
+    }
+
+    /// The subcommand named `name`, or with `name` as an alias.
+    pub fn subcommand(&self, name: &str) -> Option<&Subcommand> {
+        self.subcommands
+            .iter()
+            .find(|s| s.name == name || s.aliases.iter().any(|a| a == name))
+    }
+}
+
+/// The name of the section `line` starts, and the text following it on the same line.
+fn header(line: &str) -> Option<(&str, Option<&str>)> {
+    let colon = line.find(':')?;
+    let name = &line[..colon];
+    if name.is_empty()
+        || !name
+            .chars()
+            .all(|c| c.is_alphanumeric() || c == ' ' || c == '-')
+    {
+        return None;
+    }
+    let inline = line[colon + 1..].trim();
+    if inline.is_empty() {
+        Some((name, None))
+    } else if name.contains(' ') {
+        // Prose like `Note that ...: ...`.
+        None
+    } else {
+        Some((name, Some(inline)))
+    }
+}
+
+fn indentation(line: &str) -> usize {
+    line.len() - line.trim_start().len()
+}
+
+fn dedent(lines: &[&str]) -> Vec<String> {
+    let indent = lines
+        .iter()
+        .filter(|l| !l.is_empty())
+        .map(|l| indentation(l))
+        .min()
+        .unwrap_or(0);
+    lines
+        .iter()
+        .map(|l| l.get(indent..).unwrap_or("").to_owned())
+        .collect()
+}
+
+/// Split a section's lines into entries.
+///
+/// Lines at the section's indentation start entries, as do flags, which can be indented
+/// further to line up long flags with the long form of others.
+fn entries(lines: &[&str]) -> Vec<Entry> {
+    let indent = lines
+        .iter()
+        .filter(|l| !l.is_empty())
+        .map(|l| indentation(l))
+        .min()
+        .unwrap_or(0);
+    let mut entries: Vec<Entry> = vec![];
+    for line in lines.iter().filter(|l| !l.is_empty()) {
+        let text = line.trim_start();
+        let is_flag = text.starts_with('-') && text[1..].starts_with(|c: char| !c.is_whitespace());
+        let continues = indentation(line) > indent && !is_flag;
+        match entries.last_mut() {
+            Some(entry) if continues => entry.description.push(text.to_owned()),
+            _ => {
+                let (term, description) = match text.find("  ") {
+                    Some(gap) => (&text[..gap], vec![text[gap..].trim().to_owned()]),
+                    None => (text, vec![]),
+                };
+                entries.push(Entry {
+                    term: term.to_owned(),
+                    description,
+                });
+            }
+        }
+    }
+    entries
+}
+
+fn flag(entry: &Entry) -> Flag {
+    let mut flag = Flag {
+        short: None,
+        long: None,
+        value: None,
+        description: entry.description.join(" "),
+    };
+    let mut values = vec![];
+    for word in entry.term.split(|c: char| c == ',' || c.is_whitespace()) {
+        let word = word.trim_end_matches("...");
+        if word.is_empty() {
+            continue;
+        }
+        if !word.starts_with('-') {
+            values.push(word);
+            continue;
+        }
+        let (name, value) = match word.find(['=', '[']) {
+            Some(i) => (&word[..i], Some(word[i..].trim_start_matches('='))),
+            None => (word, None),
+        };
+        values.extend(value);
+        if name.starts_with("--") {
+            flag.long = Some(name.to_owned());
+        } else {
+            flag.short = Some(name.to_owned());
+        }
+    }
+    if !values.is_empty() {
+        flag.value = Some(values.join(" "));
+    }
+    flag
+}
+
+fn subcommand(entry: &Entry) -> Option<Subcommand> {
+    let mut names = entry
+        .term
+        .split(',')
+        .map(str::trim)
+        .filter(|name| !name.is_empty());
+    let name = names.next()?;
+    let is_word = |name: &str| {
+        name.chars()
+            .all(|c| c.is_alphanumeric() || c == '-' || c == '_')
+    };
+    if !is_word(name) {
+        return None;
+    }
+    Some(Subcommand {
+        name: name.to_owned(),
+        aliases: names.filter(|a| is_word(a)).map(str::to_owned).collect(),
+        description: entry.description.join(" "),
+        help: None,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parses_clap2_layout() {
+        let help = Help::parse(
+            "myapp 1.0
+Does awesome things
+
+USAGE:
+    myapp [FLAGS] [OPTIONS] <INPUT> [SUBCOMMAND]
+
+FLAGS:
+    -d, --debug      Turn debugging information on
+    -h, --help       Prints help information
+
+OPTIONS:
+    -c, --config <FILE>    Sets a custom config file
+        --level=<N>...     Sets the level
+
+ARGS:
+    <INPUT>    The input file
+
+SUBCOMMANDS:
+    help    Prints this message or the help of the given subcommand(s)
+    test    Controls testing features
+",
+        );
+        assert_eq!(
+            help.section_names(),
+            ["USAGE", "FLAGS", "OPTIONS", "ARGS", "SUBCOMMANDS"]
+        );
+        assert_eq!(
+            help.usage(),
+            Some("myapp [FLAGS] [OPTIONS] <INPUT> [SUBCOMMAND]")
+        );
+        assert_eq!(help.section("args"), Some("<INPUT>    The input file"));
+        let flags: Vec<_> = help.flags().iter().map(Flag::to_string).collect();
+        assert_eq!(
+            flags,
+            [
+                "-d, --debug",
+                "-h, --help",
+                "-c, --config <FILE>",
+                "--level <N>"
+            ]
+        );
+        assert_eq!(
+            help.flag("--debug").unwrap().description(),
+            "Turn debugging information on"
+        );
+        let names: Vec<_> = help.subcommands().iter().map(Subcommand::name).collect();
+        assert_eq!(names, ["help", "test"]);
+    }
+
+    #[test]
+    fn parses_long_help() {
+        let help = Help::parse(
+            "Usage: tool [OPTIONS] <COMMAND>
+       tool --list
+
+Commands:
+  build, b  Compile the current package
+  ...       See all commands with --list
+
+Options:
+  -c, --config <FILE>
+          Sets a custom config file,
+          overriding the default one
+
+      --list
+          List installed commands
+
+See 'tool help <command>' for more.
+",
+        );
+        assert_eq!(help.usage(), Some("tool [OPTIONS] <COMMAND>\ntool --list"));
+        assert_eq!(
+            help.flag("-c").unwrap().description(),
+            "Sets a custom config file, overriding the default one"
+        );
+        assert_eq!(
+            help.flag("--list").unwrap().description(),
+            "List installed commands"
+        );
+        assert_eq!(help.subcommands().len(), 1);
+        assert_eq!(help.subcommand("b").unwrap().name(), "build");
+        assert!(!help.section("options").unwrap().contains("See"));
+    }
+}
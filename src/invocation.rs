@@ -0,0 +1,117 @@
+use std::ffi::{OsStr, OsString};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use sandbox::Sandbox;
+
+/// The program, arguments, environment, working directory and stdin a command is started with.
+///
+/// See [`Assert::dry_run`](struct.Assert.html#method.dry_run) and
+/// [`Assert::show_invocation`](struct.Assert.html#method.show_invocation).
+#[derive(Debug)]
+pub struct Invocation {
+    program: OsString,
+    args: Vec<OsString>,
+    env: Vec<(OsString, OsString)>,
+    current_dir: Option<PathBuf>,
+    stdin: String,
+    stdin_len: Option<u64>,
+    /// The files the environment points to, removed on drop.
+    sandbox: Option<Sandbox>,
+}
+
+impl Invocation {
+    pub(crate) fn new(
+        cmd: &[OsString],
+        mut env: Vec<(OsString, OsString)>,
+        current_dir: Option<PathBuf>,
+        stdin: String,
+        stdin_len: Option<u64>,
+        sandbox: Option<Sandbox>,
+    ) -> Self {
+        env.sort();
+        Self {
+            program: cmd[0].clone(),
+            args: cmd[1..].to_vec(),
+            env,
+            current_dir,
+            stdin,
+            stdin_len,
+            sandbox,
+        }
+    }
+
+    /// The program to run.
+    pub fn program(&self) -> &OsStr {
+        &self.program
+    }
+
+    /// The arguments passed to the program.
+    pub fn args(&self) -> &[OsString] {
+        &self.args
+    }
+
+    /// The complete environment of the command, sorted by name.
+    pub fn env(&self) -> &[(OsString, OsString)] {
+        &self.env
+    }
+
+    /// The value of an environment variable of the command.
+    pub fn env_var<K: AsRef<OsStr>>(&self, key: K) -> Option<&OsStr> {
+        let key = key.as_ref();
+        self.env
+            .iter()
+            .find(|&(k, _)| k == key)
+            .map(|(_, v)| v.as_os_str())
+    }
+
+    /// The working directory, if not inherited.
+    pub fn current_dir(&self) -> Option<&Path> {
+        self.current_dir.as_deref()
+    }
+
+    /// Bytes written to stdin, if known up front.
+    pub fn stdin_len(&self) -> Option<u64> {
+        self.stdin_len
+    }
+
+    /// Whether the command runs in a sandbox, see [`Assert::hermetic`](struct.Assert.html#method.hermetic).
+    pub fn is_hermetic(&self) -> bool {
+        self.sandbox.is_some()
+    }
+
+    pub(crate) fn command(&self) -> Command {
+        let mut command = Command::new(&self.program);
+        command
+            .args(&self.args)
+            .env_clear()
+            .envs(self.env.iter().map(|(k, v)| (k, v)));
+        if let Some(ref dir) = self.current_dir {
+            command.current_dir(dir);
+        }
+        command
+    }
+}
+
+impl fmt::Display for Invocation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "program: {}", self.program.to_string_lossy())?;
+        writeln!(f, "args: {:?}", self.args)?;
+        match self.current_dir {
+            Some(ref dir) => writeln!(f, "cwd: {}", dir.display())?,
+            None => writeln!(f, "cwd: inherited")?,
+        }
+        writeln!(f, "stdin: {}", self.stdin)?;
+        write!(f, "env:")?;
+        for (key, value) in &self.env {
+            write!(
+                f,
+                "\n  {}={}",
+                key.to_string_lossy(),
+                value.to_string_lossy()
+            )?;
+        }
+        Ok(())
+    }
+}
@@ -37,6 +37,15 @@
 //! +42
 //! ```
 //!
+//! Colors are only used when stderr is a terminal, unless overridden through
+//! `NO_COLOR`, `CLICOLOR_FORCE` or `Assert::color`.
+//!
+//! Directories the command writes can be compared against expected ones with `Assert::dir`.
+//! Set `ASSERT_CLI_UPDATE=1` to overwrite the expected directories instead.
+//!
+//! With the `tokio` feature, `Assert::execute_async` checks a command from async tests without
+//! blocking the runtime.
+//!
 //! ## `assert_cmd!` Macro
 //!
 //! Alternatively, you can use the `assert_cmd!` macro to construct the command more conveniently,
@@ -55,6 +64,10 @@
 //! - Enclose arguments in the `assert_cmd!` macro in quotes `"`,
 //!   if there are special characters, which the macro doesn't accept, e.g.
 //!   `assert_cmd!(cat "foo.txt")`.
+//! - Set environment variables, stdin and the working directory like
+//!   `assert_cmd!(LANG="C" sort < "b\na\n" in "tests")`, and use Rust
+//!   expressions as `{expr}`.
+//! - To paste a command line from elsewhere as is, use `Assert::parse_command`.
 //!
 //! ## Exit Status
 //!
@@ -73,7 +86,9 @@
 //!
 //! Some notes on this:
 //!
-//! - Use `fails_with` to assert a specific exit status.
+//! - Use `fails_with` to assert a specific exit status, or `exit_code_in`,
+//!   `exit_code_one_of` and `exit_code_not` to accept several.
+//! - `fails_with_named` expects a code by name, like `EX_USAGE` from `sysexits.h`.
 //! - There is also a `succeeds` method, but this is already the implicit default
 //!   and can usually be omitted.
 //! - The `and` method has no effect, other than to make everything more readable.
@@ -117,14 +132,19 @@
 
 #![deny(missing_docs)]
 
-extern crate colored;
 extern crate difference;
 extern crate environment;
 #[macro_use]
 extern crate failure;
 #[macro_use]
 extern crate failure_derive;
+#[cfg(unix)]
+extern crate libc;
+extern crate regex;
 extern crate serde_json;
+extern crate tempfile;
+#[cfg(feature = "tokio")]
+extern crate tokio;
 
 mod errors;
 pub use errors::AssertionError;
@@ -133,13 +153,48 @@ pub use errors::AssertionError;
 mod macros;
 pub use macros::flatten_escaped_string;
 
+mod ansi;
 mod assert;
+#[cfg(feature = "tokio")]
+mod async_exec;
+mod batch;
+mod capture;
+mod color;
+mod determinism;
 mod diff;
+mod exit_code;
+mod golden;
+mod help;
+mod invocation;
 mod output;
+mod pty;
+mod rlimit;
+mod rusage;
+mod sandbox;
+mod scenario;
+mod shell;
+mod stdin;
+mod template;
 
+pub use ansi::{Color, Style};
 pub use assert::Assert;
+pub use assert::DirAssertionBuilder;
+pub use assert::Outcome;
 pub use assert::OutputAssertionBuilder;
+#[cfg(feature = "tokio")]
+pub use async_exec::ExecuteAsync;
+pub use batch::{Batch, BatchReport};
+pub use color::ColorChoice;
+pub use diff::DiffStyle;
 /// Environment is a re-export of the Environment crate
 ///
 /// It allow you to define/override environment variables for one or more assertions.
 pub use environment::Environment;
+pub use exit_code::ExitCodeNames;
+pub use help::{Flag, Help, Subcommand};
+pub use invocation::Invocation;
+pub use rlimit::RLimit;
+pub use rusage::ResourceUsage;
+pub use scenario::{Scenario, ScenarioError, Step, StepRecord, Transcript};
+pub use shell::ParseCommandError;
+pub use template::CommandTemplate;
@@ -30,19 +30,94 @@ use std::borrow::Cow;
 /// The macro will try to convert its arguments as strings, but is limited by
 /// Rust's default tokenizer, e.g., you always need to quote CLI arguments
 /// like `"--verbose"`.
+///
+/// Besides the command, the macro accepts
+///
+/// - `NAME=value` before the command, to set environment variables,
+/// - `{expr}` anywhere a value is expected, to use a Rust expression instead of a token,
+/// - `< value` after the arguments, to set stdin,
+/// - `in value` after the arguments, to set the working directory.
+///
+/// Quote `"<"` and `"in"` to pass them as arguments.
+///
+/// ```rust
+/// #[macro_use] extern crate assert_cli;
+/// # fn main() {
+/// let dir = std::env::temp_dir();
+/// assert_cmd!(GREETING="hello" sh "-c" "echo $GREETING $0; cat; pwd" {"world"} < "input\n" in {&dir})
+///     .stdout().contains("hello world\ninput")
+///     .stdout().contains(dir.file_name().unwrap().to_str().unwrap())
+///     .unwrap();
+/// # }
+/// ```
 #[macro_export]
 macro_rules! assert_cmd {
     ($($x:tt)+) => {{
-        $(__assert_single_token_expression!(@CHECK $x);)*
-
-        $crate::Assert::command(
-            &[$(
-                $crate::flatten_escaped_string(stringify!($x)).as_ref()
-            ),*]
-        )
+        __assert_cmd!(@ENV [] $($x)+)
     }}
 }
 
+/// Munch the tokens of `assert_cmd!`, see there for the syntax.
+///
+/// Environment variables come first, then the arguments, then the `<` and `in` clauses in any
+/// order.  Values are either `{expr}` or a single token checked by
+/// `__assert_single_token_expression!`, so errors point at the offending token.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __assert_cmd {
+    (@ENV [$($env:tt)*] $name:ident = $value:tt $($rest:tt)+) => {
+        __assert_cmd!(@ENV [$($env)* ($name $value)] $($rest)+)
+    };
+    (@ENV $env:tt $($rest:tt)+) => {
+        __assert_cmd!(@ARGS $env [] $($rest)+)
+    };
+
+    (@ARGS $env:tt $args:tt < $($rest:tt)+) => {
+        __assert_cmd!(@OPTS $env $args [] < $($rest)+)
+    };
+    (@ARGS $env:tt $args:tt in $($rest:tt)+) => {
+        __assert_cmd!(@OPTS $env $args [] in $($rest)+)
+    };
+    (@ARGS $env:tt [$($args:tt)*] $arg:tt $($rest:tt)*) => {
+        __assert_cmd!(@ARGS $env [$($args)* $arg] $($rest)*)
+    };
+    (@ARGS $env:tt $args:tt) => {
+        __assert_cmd!(@OPTS $env $args [])
+    };
+
+    (@OPTS $env:tt $args:tt [$($opts:tt)*] < $stdin:tt $($rest:tt)*) => {
+        __assert_cmd!(@OPTS $env $args [$($opts)* (stdin $stdin)] $($rest)*)
+    };
+    (@OPTS $env:tt $args:tt [$($opts:tt)*] in $dir:tt $($rest:tt)*) => {
+        __assert_cmd!(@OPTS $env $args [$($opts)* (current_dir $dir)] $($rest)*)
+    };
+    (@OPTS [$(($name:ident $value:tt))*] [$($arg:tt)+] [$(($opt:ident $opt_value:tt))*]) => {{
+        $(__assert_cmd!(@CHECK $value);)*
+        $(__assert_cmd!(@CHECK $arg);)+
+        $(__assert_cmd!(@CHECK $opt_value);)*
+
+        $crate::Assert::command(&[$(__assert_cmd!(@VALUE $arg)),+])
+            .with_env(
+                $crate::Environment::inherit()
+                    $(.insert(stringify!($name), __assert_cmd!(@VALUE $value)))*
+            )
+            $(.$opt(__assert_cmd!(@$opt $opt_value)))*
+    }};
+
+    (@CHECK {$($e:tt)+}) => { };
+    (@CHECK $x:tt) => { __assert_single_token_expression!(@CHECK $x) };
+
+    (@VALUE {$($e:tt)+}) => {
+        ::std::ffi::OsStr::new(&($($e)+)).to_os_string()
+    };
+    (@VALUE $x:tt) => {
+        ::std::ffi::OsString::from($crate::flatten_escaped_string(stringify!($x)).into_owned())
+    };
+    (@stdin {$($e:tt)+}) => { $($e)+ };
+    (@stdin $x:tt) => { $crate::flatten_escaped_string(stringify!($x)).into_owned() };
+    (@current_dir $x:tt) => { __assert_cmd!(@VALUE $x) };
+}
+
 /// Deserialize a JSON-encoded `String`.
 ///
 /// # Panics
@@ -85,14 +160,17 @@ pub fn flatten_escaped_string(x: &str) -> Cow<str> {
 #[doc(hidden)]
 #[macro_export]
 macro_rules! __assert_single_token_expression {
+    // keep the token around, so errors point at it
+    (@CHECK $x:tt) => { __assert_single_token_expression!(@GROUP $x $x) };
     // deny `{...}`
-    (@CHECK {$( $x:tt )*}) => { assert_cmd!(@DENY {$( $x )*}) };
+    (@GROUP {$( $_i:tt )*} $x:tt) => { __assert_single_token_expression!(@DENY $x) };
     // deny `(...)`
-    (@CHECK ($( $x:tt )*)) => { assert_cmd!(@DENY {$( $x )*}) };
+    (@GROUP ($( $_i:tt )*) $x:tt) => { __assert_single_token_expression!(@DENY $x) };
     // deny `[...]`
-    (@CHECK [$( $x:tt )*]) => { assert_cmd!(@DENY {$( $x )*}) };
+    (@GROUP [$( $_i:tt )*] $x:tt) => { __assert_single_token_expression!(@DENY $x) };
     // only allow tokens that parse as expression
-    (@CHECK $x:expr) => { };
+    (@GROUP $x:tt $_x:tt) => { __assert_single_token_expression!(@EXPR $x) };
+    (@EXPR $x:expr) => { };
     // little helper
     (@DENY) => { };
 }
@@ -118,4 +196,20 @@ mod test {
             "hello world B A"
         );
     }
+
+    #[test]
+    fn clauses() {
+        let name = String::from("world");
+        let dir = ::std::env::temp_dir();
+        assert_cmd!(A="1" B={name} sh "-c" "echo $A $B $0 $1; cat; pwd" {&name} "<" < {format!("in{}\n", 42)} in {&dir})
+            .stdout()
+            .is(format!("1 world world <\nin42\n{}", dir.display()).as_str())
+            .unwrap();
+    }
+
+    #[test]
+    fn clauses_in_any_order() {
+        assert_cmd!(cat in "/" < "x").stdout().is("x").unwrap();
+        assert_cmd!(pwd in "/").stdout().is("/").unwrap();
+    }
 }
@@ -1,3 +1,4 @@
+use std::borrow::Cow;
 use std::fmt;
 use std::process;
 use std::rc;
@@ -5,6 +6,7 @@ use std::rc;
 use difference::Changeset;
 use failure;
 
+use ansi::{self, Style};
 use diff;
 use errors::*;
 
@@ -42,21 +44,31 @@ struct IsPredicate {
 }
 
 impl IsPredicate {
-    pub fn verify(&self, got: &[u8]) -> Result<(), failure::Error> {
+    pub fn verify(&self, got: &[u8], renderer: &diff::Renderer) -> Result<(), failure::Error> {
         match self.expect {
             Content::Str(ref expect) => {
-                self.verify_str(expect, String::from_utf8_lossy(got).as_ref())
+                self.verify_str(expect, String::from_utf8_lossy(got).as_ref(), renderer)
             }
-            Content::Bytes(ref expect) => self.verify_bytes(expect, got),
+            Content::Bytes(ref expect) => self.verify_bytes(expect, got, renderer),
         }
     }
 
-    fn verify_bytes(&self, expect: &[u8], got: &[u8]) -> Result<(), failure::Error> {
+    fn verify_bytes(
+        &self,
+        expect: &[u8],
+        got: &[u8],
+        renderer: &diff::Renderer,
+    ) -> Result<(), failure::Error> {
         let result = expect == got;
 
         if result != self.expected_result {
             if self.expected_result {
-                bail!(BytesDoesntMatch::new(expect.to_owned(), got.to_owned()));
+                let hexdump = renderer.render_bytes(expect, got)?;
+                bail!(BytesDoesntMatch::new(
+                    expect.to_owned(),
+                    got.to_owned(),
+                    hexdump
+                ));
             } else {
                 bail!(BytesMatches::new(got.to_owned()));
             }
@@ -65,13 +77,18 @@ impl IsPredicate {
         Ok(())
     }
 
-    fn verify_str(&self, expect: &str, got: &str) -> Result<(), failure::Error> {
+    fn verify_str(
+        &self,
+        expect: &str,
+        got: &str,
+        renderer: &diff::Renderer,
+    ) -> Result<(), failure::Error> {
         let differences = Changeset::new(expect.trim(), got.trim(), "\n");
         let result = differences.distance == 0;
 
         if result != self.expected_result {
             if self.expected_result {
-                let nice_diff = diff::render(&differences)?;
+                let nice_diff = renderer.render(&differences)?;
                 bail!(StrDoesntMatch::new(
                     expect.to_owned(),
                     got.to_owned(),
@@ -127,6 +144,20 @@ impl ContainsPredicate {
         Ok(())
     }
 
+    /// Whether the needle occurs in `got`, skipping matches that would end within the first
+    /// `seen` bytes, which were searched before.
+    pub fn found_since(&self, got: &[u8], seen: usize) -> bool {
+        let needle = match self.expect {
+            Content::Str(ref expect) => expect.as_bytes(),
+            Content::Bytes(ref expect) => expect.as_slice(),
+        };
+        if needle.is_empty() {
+            return true;
+        }
+        let start = (seen + 1).saturating_sub(needle.len());
+        find_subsequence(&got[start..], needle).is_some()
+    }
+
     pub fn verify_str(&self, expect: &str, got: &str) -> Result<(), failure::Error> {
         let result = got.contains(expect);
         if result != self.expected_result {
@@ -143,7 +174,7 @@ impl ContainsPredicate {
 
 #[derive(Clone)]
 struct FnPredicate {
-    pub pred: rc::Rc<Fn(&str) -> bool>,
+    pub pred: rc::Rc<dyn Fn(&str) -> bool>,
     pub msg: String,
 }
 
@@ -165,19 +196,83 @@ impl fmt::Debug for FnPredicate {
     }
 }
 
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct LenPredicate {
+    pub max: usize,
+}
+
+impl LenPredicate {
+    pub fn verify(&self, got: &[u8]) -> Result<(), failure::Error> {
+        if got.len() > self.max {
+            bail!(OutputTooLargeError::new(self.max, got.len(), true, got));
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum AnsiPredicate {
+    NoEscapes,
+    StyledContains(String, Style),
+}
+
+impl AnsiPredicate {
+    pub fn verify(&self, got: &[u8]) -> Result<(), failure::Error> {
+        match *self {
+            AnsiPredicate::NoEscapes => {
+                if let Some(position) = ansi::find_escape(got) {
+                    bail!(AnsiFound::new(position, got));
+                }
+            }
+            AnsiPredicate::StyledContains(ref text, ref style) => {
+                if text.is_empty() {
+                    return Ok(());
+                }
+                let styled = ansi::parse(got);
+                let mut found = None;
+                let mut start = 0;
+                while let Some(offset) = find_subsequence(&styled.text[start..], text.as_bytes()) {
+                    let position = start + offset;
+                    let styles = &styled.styles[position..position + text.len()];
+                    match styles.iter().find(|s| !s.satisfies(style)) {
+                        None => return Ok(()),
+                        Some(&other) => {
+                            found.get_or_insert(other);
+                        }
+                    }
+                    start = position + 1;
+                }
+                bail!(StyleMismatch::new(
+                    text.clone(),
+                    *style,
+                    found,
+                    String::from_utf8_lossy(&styled.text).into_owned()
+                ));
+            }
+        }
+
+        Ok(())
+    }
+}
+
 #[derive(Debug, Clone)]
 enum ContentPredicate {
     Is(IsPredicate),
     Contains(ContainsPredicate),
     Fn(FnPredicate),
+    Len(LenPredicate),
+    Ansi(AnsiPredicate),
 }
 
 impl ContentPredicate {
-    pub fn verify(&self, got: &[u8]) -> Result<(), failure::Error> {
+    pub fn verify(&self, got: &[u8], renderer: &diff::Renderer) -> Result<(), failure::Error> {
         match *self {
-            ContentPredicate::Is(ref pred) => pred.verify(got),
+            ContentPredicate::Is(ref pred) => pred.verify(got, renderer),
             ContentPredicate::Contains(ref pred) => pred.verify(got),
             ContentPredicate::Fn(ref pred) => pred.verify(got),
+            ContentPredicate::Len(ref pred) => pred.verify(got),
+            ContentPredicate::Ansi(ref pred) => pred.verify(got),
         }
     }
 }
@@ -292,16 +387,105 @@ impl Output {
         Self::new(ContentPredicate::Fn(pred))
     }
 
+    /// Expect the command's output to be at most `max` bytes long.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// extern crate assert_cli;
+    ///
+    /// assert_cli::Assert::command(&["echo", "42"])
+    ///     .stdout().len_at_most(3)
+    ///     .unwrap();
+    /// ```
+    pub fn len_at_most(max: usize) -> Self {
+        Self::new(ContentPredicate::Len(LenPredicate { max }))
+    }
+
+    /// Expect the command to not output anything.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// extern crate assert_cli;
+    ///
+    /// assert_cli::Assert::command(&["echo", "42"])
+    ///     .stderr().is_empty()
+    ///     .unwrap();
+    /// ```
+    pub fn is_empty() -> Self {
+        Self::len_at_most(0)
+    }
+
+    /// Expect the command's output to not contain ANSI escape sequences.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// extern crate assert_cli;
+    ///
+    /// assert_cli::Assert::command(&["echo", "42"])
+    ///     .stdout().has_no_ansi()
+    ///     .unwrap();
+    /// ```
+    pub fn has_no_ansi() -> Self {
+        Self::new(ContentPredicate::Ansi(AnsiPredicate::NoEscapes))
+    }
+
+    /// Expect the command's output to contain `text` in `style`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// extern crate assert_cli;
+    /// use assert_cli::{Assert, Style};
+    ///
+    /// Assert::command(&["printf", "\\033[32mok\\033[0m"])
+    ///     .stdout().styled_contains("ok", Style::green())
+    ///     .unwrap();
+    /// ```
+    pub fn styled_contains<S: Into<String>>(text: S, style: Style) -> Self {
+        let pred = AnsiPredicate::StyledContains(text.into(), style);
+        Self::new(ContentPredicate::Ansi(pred))
+    }
+
     fn new(pred: ContentPredicate) -> Self {
         Self { pred }
     }
 
-    pub(crate) fn verify(&self, got: &[u8]) -> Result<(), failure::Error> {
-        self.pred.verify(got)
+    pub(crate) fn verify(
+        &self,
+        got: &[u8],
+        renderer: &diff::Renderer,
+    ) -> Result<(), failure::Error> {
+        self.pred.verify(got, renderer)
+    }
+
+    /// Check output that is still growing, failing only when more output can't change the
+    /// outcome, i.e. when a forbidden needle already showed up.
+    pub(crate) fn verify_partial(&self, got: &[u8], seen: usize) -> Result<(), failure::Error> {
+        match self.pred {
+            ContentPredicate::Contains(ref pred)
+                if !pred.expected_result && pred.found_since(got, seen) =>
+            {
+                pred.verify(got)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Whether a `contains` predicate is satisfied by output that is still growing.
+    pub(crate) fn found_since(&self, got: &[u8], seen: usize) -> bool {
+        match self.pred {
+            ContentPredicate::Contains(ref pred) if pred.expected_result => {
+                pred.found_since(got, seen)
+            }
+            _ => false,
+        }
     }
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum OutputKind {
     StdOut,
     StdErr,
@@ -325,24 +509,120 @@ impl fmt::Display for OutputKind {
     }
 }
 
+impl fmt::Display for ContentPredicate {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ContentPredicate::Is(ref pred) if pred.expected_result => {
+                write!(f, "is ```{:?}```", pred.expect)
+            }
+            ContentPredicate::Is(ref pred) => write!(f, "isn't ```{:?}```", pred.expect),
+            ContentPredicate::Contains(ref pred) if pred.expected_result => {
+                write!(f, "contains ```{:?}```", pred.expect)
+            }
+            ContentPredicate::Contains(ref pred) => {
+                write!(f, "doesn't contain ```{:?}```", pred.expect)
+            }
+            ContentPredicate::Fn(ref pred) => write!(f, "satisfies {:?}", pred),
+            ContentPredicate::Len(ref pred) => write!(f, "is at most {} bytes", pred.max),
+            ContentPredicate::Ansi(AnsiPredicate::NoEscapes) => write!(f, "has no ANSI escapes"),
+            ContentPredicate::Ansi(AnsiPredicate::StyledContains(ref text, ref style)) => {
+                write!(f, "contains ```{}``` in {}", text, style)
+            }
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct OutputPredicate {
     kind: OutputKind,
     pred: Output,
+    stop: bool,
+    stripped: bool,
 }
 
 impl OutputPredicate {
     pub fn new(kind: OutputKind, pred: Output) -> Self {
-        Self { kind, pred }
+        Self {
+            kind,
+            pred,
+            stop: false,
+            stripped: false,
+        }
+    }
+
+    /// A predicate that stops the command once it is satisfied.
+    pub fn stopping(kind: OutputKind, pred: Output) -> Self {
+        Self {
+            kind,
+            pred,
+            stop: true,
+            stripped: false,
+        }
+    }
+
+    /// Check the output without ANSI escape sequences.
+    pub fn stripped(mut self) -> Self {
+        self.stripped = true;
+        self
+    }
+
+    fn view<'a>(&self, got: &'a [u8]) -> Cow<'a, [u8]> {
+        if self.stripped {
+            ansi::strip(got)
+        } else {
+            Cow::Borrowed(got)
+        }
     }
 
-    pub(crate) fn verify(&self, got: &process::Output) -> Result<(), OutputError> {
-        let got = self.kind.select(got);
-        self.pred.verify(got).chain(OutputError::new(self.kind))?;
+    pub(crate) fn kind(&self) -> OutputKind {
+        self.kind
+    }
+
+    pub(crate) fn stops(&self) -> bool {
+        self.stop
+    }
+
+    pub(crate) fn verify_partial(&self, got: &[u8], seen: usize) -> Result<(), OutputError> {
+        // Offsets into the raw output don't apply to the stripped one.
+        let seen = if self.stripped { 0 } else { seen };
+        self.pred
+            .verify_partial(&self.view(got), seen)
+            .chain(OutputError::new(self.kind))
+    }
+
+    pub(crate) fn found_since(&self, got: &[u8], seen: usize) -> bool {
+        let seen = if self.stripped { 0 } else { seen };
+        self.pred.found_since(&self.view(got), seen)
+    }
+
+    /// Whether the assertion holds, without building an error.
+    pub(crate) fn holds(&self, got: &process::Output, renderer: &diff::Renderer) -> bool {
+        let got = self.view(self.kind.select(got));
+        self.pred.verify(&got, renderer).is_ok()
+    }
+
+    pub(crate) fn verify(
+        &self,
+        got: &process::Output,
+        renderer: &diff::Renderer,
+    ) -> Result<(), OutputError> {
+        let got = self.view(self.kind.select(got));
+        self.pred
+            .verify(&got, renderer)
+            .chain(OutputError::new(self.kind))?;
         Ok(())
     }
 }
 
+impl fmt::Display for OutputPredicate {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.stripped {
+            write!(f, "stripped ")?;
+        }
+        write!(f, "{} {}", self.kind, self.pred.pred)
+    }
+}
+
 #[derive(Fail, Debug)]
 pub struct StrDoesntContain {
     needle: String,
@@ -451,19 +731,29 @@ impl fmt::Display for StrDoesntMatch {
 pub struct BytesDoesntMatch {
     expected: Vec<u8>,
     got: Vec<u8>,
+    hexdump: String,
 }
 
 impl BytesDoesntMatch {
-    pub fn new(expected: Vec<u8>, got: Vec<u8>) -> Self {
-        Self { expected, got }
+    pub fn new(expected: Vec<u8>, got: Vec<u8>, hexdump: String) -> Self {
+        Self {
+            expected,
+            got,
+            hexdump,
+        }
     }
 }
 
 impl fmt::Display for BytesDoesntMatch {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "Didn't match.\n")?;
-        write!(f, "expected=```{:?}```\n", self.expected)?;
-        write!(f, "got=```{:?}```", self.got)
+        writeln!(
+            f,
+            "expected {} bytes, got {} bytes",
+            self.expected.len(),
+            self.got.len()
+        )?;
+        write!(f, "hexdump=\n```{}```", self.hexdump)
     }
 }
 
@@ -522,6 +812,114 @@ impl fmt::Display for PredicateFailed {
     }
 }
 
+#[derive(Debug)]
+pub struct AnsiFound {
+    position: usize,
+    got: String,
+}
+
+impl AnsiFound {
+    pub fn new(position: usize, got: &[u8]) -> Self {
+        Self {
+            position,
+            got: String::from_utf8_lossy(got).replace('\x1b', "\\x1b"),
+        }
+    }
+}
+
+impl failure::Fail for AnsiFound {}
+
+impl fmt::Display for AnsiFound {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "Expected no ANSI escapes, found one at byte {}.",
+            self.position
+        )?;
+        write!(f, "output=```{}```", self.got)
+    }
+}
+
+#[derive(Debug)]
+pub struct StyleMismatch {
+    text: String,
+    expected: Style,
+    found: Option<Style>,
+    plain: String,
+}
+
+impl StyleMismatch {
+    pub fn new(text: String, expected: Style, found: Option<Style>, plain: String) -> Self {
+        Self {
+            text,
+            expected,
+            found,
+            plain,
+        }
+    }
+}
+
+impl failure::Fail for StyleMismatch {}
+
+impl fmt::Display for StyleMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Expected ```{}``` in {}", self.text, self.expected)?;
+        match self.found {
+            Some(found) => writeln!(f, ", found it {}.", found)?,
+            None => writeln!(f, ", didn't find it.")?,
+        }
+        write!(f, "output without escapes=```{}```", self.plain)
+    }
+}
+
+/// Number of bytes of oversized output shown in failures.
+const TOO_LARGE_PREVIEW: usize = 1024;
+
+#[derive(Debug)]
+pub struct OutputTooLargeError {
+    max: usize,
+    len: usize,
+    exact: bool,
+    start: Vec<u8>,
+}
+
+impl OutputTooLargeError {
+    pub fn new(max: usize, len: usize, exact: bool, output: &[u8]) -> Self {
+        let start = output.iter().take(TOO_LARGE_PREVIEW).cloned().collect();
+        Self {
+            max,
+            len,
+            exact,
+            start,
+        }
+    }
+}
+
+impl failure::Fail for OutputTooLargeError {}
+
+impl fmt::Display for OutputTooLargeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "Expected at most {} bytes, got {}{} bytes.",
+            self.max,
+            if self.exact { "" } else { "at least " },
+            self.len
+        )?;
+        let more = if self.len > self.start.len() {
+            "..."
+        } else {
+            ""
+        };
+        write!(
+            f,
+            "output=```{}{}```",
+            String::from_utf8_lossy(&self.start),
+            more
+        )
+    }
+}
+
 #[derive(Debug)]
 pub struct OutputError {
     kind: OutputKind,
@@ -0,0 +1,152 @@
+use std::ffi::OsString;
+use std::fs::File;
+use std::io::{self, Read};
+use std::process::Stdio;
+
+/// Which streams to attach to a terminal, and its size.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Tty {
+    pub rows: u16,
+    pub cols: u16,
+    pub stdout: bool,
+    pub stderr: bool,
+}
+
+impl Tty {
+    /// `COLUMNS` and `LINES`, matching the terminal size.
+    pub fn env(&self) -> Vec<(OsString, OsString)> {
+        vec![
+            ("COLUMNS".into(), self.cols.to_string().into()),
+            ("LINES".into(), self.rows.to_string().into()),
+        ]
+    }
+
+    /// Open a terminal if `attached`, `None` leaves the stream to a pipe.
+    pub fn open(&self, attached: bool) -> io::Result<Option<Pty>> {
+        if attached {
+            Pty::open(self.rows, self.cols).map(Some)
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+/// A pseudo-terminal, the command writing to one end and us reading from the other.
+#[derive(Debug)]
+pub(crate) struct Pty {
+    master: File,
+    slave: File,
+}
+
+impl Pty {
+    #[cfg(unix)]
+    fn open(rows: u16, cols: u16) -> io::Result<Self> {
+        use libc;
+        use std::os::unix::io::FromRawFd;
+        use std::ptr;
+
+        let size = libc::winsize {
+            ws_row: rows,
+            ws_col: cols,
+            ws_xpixel: 0,
+            ws_ypixel: 0,
+        };
+        let mut master = 0;
+        let mut slave = 0;
+        // Safety: the pointers are valid for the duration of the call, null ones are optional.
+        let opened = unsafe {
+            libc::openpty(
+                &mut master,
+                &mut slave,
+                ptr::null_mut(),
+                ptr::null_mut(),
+                &size as *const _ as *mut _,
+            )
+        };
+        if opened != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        // Safety: `openpty` handed us both descriptors.
+        let pty = unsafe {
+            Self {
+                master: File::from_raw_fd(master),
+                slave: File::from_raw_fd(slave),
+            }
+        };
+        pty.configure()?;
+        Ok(pty)
+    }
+
+    #[cfg(not(unix))]
+    fn open(_rows: u16, _cols: u16) -> io::Result<Self> {
+        Err(io::Error::new(
+            io::ErrorKind::Other,
+            "Can't emulate a terminal on this platform",
+        ))
+    }
+
+    /// Keep our ends from leaking into commands, and `\n` from turning into `\r\n`.
+    #[cfg(unix)]
+    fn configure(&self) -> io::Result<()> {
+        use libc;
+        use std::mem;
+        use std::os::unix::io::AsRawFd;
+
+        let check = |result| {
+            if result == -1 {
+                Err(io::Error::last_os_error())
+            } else {
+                Ok(())
+            }
+        };
+        // Safety: the descriptors are open and `termios` is valid for the duration of the calls.
+        unsafe {
+            for fd in &[self.master.as_raw_fd(), self.slave.as_raw_fd()] {
+                check(libc::fcntl(*fd, libc::F_SETFD, libc::FD_CLOEXEC))?;
+            }
+            let mut termios: libc::termios = mem::zeroed();
+            check(libc::tcgetattr(self.slave.as_raw_fd(), &mut termios))?;
+            termios.c_oflag &= !libc::OPOST;
+            check(libc::tcsetattr(
+                self.slave.as_raw_fd(),
+                libc::TCSANOW,
+                &termios,
+            ))
+        }
+    }
+
+    /// The end to give to the command.
+    pub fn stdio(&self) -> io::Result<Stdio> {
+        Ok(self.slave.try_clone()?.into())
+    }
+
+    /// Close our copy of the command's end and read what the command writes.
+    ///
+    /// The command's copies must be closed too for reading to end, so drop the `Command`
+    /// holding them once spawned.
+    pub fn into_reader(self) -> PtyReader {
+        PtyReader(self.master)
+    }
+
+    /// Like [`into_reader`](#method.into_reader), for reading asynchronously, where `EIO`
+    /// needs to be taken as the end too.
+    #[cfg(feature = "tokio")]
+    pub fn into_master(self) -> File {
+        self.master
+    }
+}
+
+/// The output of a command through a terminal.
+#[derive(Debug)]
+pub(crate) struct PtyReader(File);
+
+impl Read for PtyReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self.0.read(buf) {
+            // Linux reports a terminal whose other end is closed with `EIO` instead of EOF.
+            #[cfg(unix)]
+            Err(ref e) if e.raw_os_error() == Some(::libc::EIO) => Ok(0),
+            result => result,
+        }
+    }
+}
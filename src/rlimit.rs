@@ -0,0 +1,243 @@
+use std::fmt;
+use std::io;
+use std::process::{Command, ExitStatus};
+
+use failure;
+
+use errors::ChainFail;
+
+/// A per-process resource limit, set on the command with `setrlimit` before it starts.
+///
+/// Only the soft limit is lowered, so the command sees the usual signal or error when it hits
+/// the limit.  Limits are only supported on Unix.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RLimit {
+    /// Highest file descriptor number plus one, `RLIMIT_NOFILE`.
+    OpenFiles,
+    /// Size of the virtual address space in bytes, `RLIMIT_AS`.
+    AddressSpace,
+    /// CPU time in seconds, `RLIMIT_CPU`.
+    CpuTime,
+    /// Size of core dumps in bytes, `RLIMIT_CORE`.
+    CoreSize,
+    /// Size of files the command may create in bytes, `RLIMIT_FSIZE`.
+    FileSize,
+    /// Number of processes of the user, `RLIMIT_NPROC`.
+    Processes,
+    /// Size of the main thread's stack in bytes, `RLIMIT_STACK`.
+    Stack,
+}
+
+impl RLimit {
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            RLimit::OpenFiles => "RLIMIT_NOFILE",
+            RLimit::AddressSpace => "RLIMIT_AS",
+            RLimit::CpuTime => "RLIMIT_CPU",
+            RLimit::CoreSize => "RLIMIT_CORE",
+            RLimit::FileSize => "RLIMIT_FSIZE",
+            RLimit::Processes => "RLIMIT_NPROC",
+            RLimit::Stack => "RLIMIT_STACK",
+        }
+    }
+
+    /// Whether hitting the limit can kill the command with `signal`.
+    #[cfg(unix)]
+    fn explains(self, signal: i32) -> bool {
+        use libc;
+
+        match self {
+            RLimit::CpuTime => signal == libc::SIGXCPU || signal == libc::SIGKILL,
+            RLimit::FileSize => signal == libc::SIGXFSZ,
+            RLimit::Stack => signal == libc::SIGSEGV,
+            RLimit::AddressSpace => signal == libc::SIGSEGV || signal == libc::SIGABRT,
+            RLimit::OpenFiles | RLimit::CoreSize | RLimit::Processes => false,
+        }
+    }
+
+    #[cfg(unix)]
+    fn resource(self) -> Resource {
+        use libc;
+
+        match self {
+            RLimit::OpenFiles => libc::RLIMIT_NOFILE,
+            RLimit::AddressSpace => libc::RLIMIT_AS,
+            RLimit::CpuTime => libc::RLIMIT_CPU,
+            RLimit::CoreSize => libc::RLIMIT_CORE,
+            RLimit::FileSize => libc::RLIMIT_FSIZE,
+            RLimit::Processes => libc::RLIMIT_NPROC,
+            RLimit::Stack => libc::RLIMIT_STACK,
+        }
+    }
+}
+
+#[cfg(all(target_os = "linux", target_env = "gnu"))]
+type Resource = ::libc::__rlimit_resource_t;
+#[cfg(all(unix, not(all(target_os = "linux", target_env = "gnu"))))]
+type Resource = ::libc::c_int;
+
+/// Set `limits` on the command, once it has forked.
+///
+/// The limits are checked against the current hard limits first, since an unprivileged process
+/// can't raise those, and the child could only report a bare `EPERM`.
+#[cfg(unix)]
+#[allow(clippy::useless_conversion)] // `rlim_t` is narrower than `u64` on some targets.
+pub(crate) fn apply(command: &mut Command, limits: &[(RLimit, u64)]) -> Result<(), RLimitError> {
+    use libc;
+    use std::os::unix::process::CommandExt;
+
+    let mut values = Vec::with_capacity(limits.len());
+    for &(limit, value) in limits {
+        let mut current = libc::rlimit {
+            rlim_cur: 0,
+            rlim_max: 0,
+        };
+        // Safety: `current` is valid for the duration of the call.
+        if unsafe { libc::getrlimit(limit.resource(), &mut current) } != 0 {
+            let err = io::Error::last_os_error();
+            return Err(RLimitError::new(limit, value, Reason::Failed).chain(err));
+        }
+        let hard = current.rlim_max;
+        if hard != libc::RLIM_INFINITY && value > u64::from(hard) {
+            return Err(RLimitError::new(
+                limit,
+                value,
+                Reason::AboveHardLimit(u64::from(hard)),
+            ));
+        }
+        let wanted = libc::rlimit {
+            rlim_cur: value as libc::rlim_t,
+            rlim_max: hard,
+        };
+        values.push((limit.resource(), wanted));
+    }
+
+    // Safety: `setrlimit` is async-signal-safe, and the closure doesn't allocate.
+    unsafe {
+        command.pre_exec(move || {
+            for &(resource, ref wanted) in &values {
+                if libc::setrlimit(resource, wanted) != 0 {
+                    return Err(io::Error::last_os_error());
+                }
+            }
+            Ok(())
+        });
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub(crate) fn apply(_command: &mut Command, limits: &[(RLimit, u64)]) -> Result<(), RLimitError> {
+    match limits.first() {
+        Some(&(limit, value)) => Err(RLimitError::new(limit, value, Reason::Unavailable)),
+        None => Ok(()),
+    }
+}
+
+/// The limit that likely killed the command, if it was killed by a signal.
+#[cfg(unix)]
+pub(crate) fn hit(limits: &[(RLimit, u64)], status: ExitStatus) -> Option<RLimitError> {
+    use std::os::unix::process::ExitStatusExt;
+
+    let signal = status.signal()?;
+    limits
+        .iter()
+        .find(|&&(limit, _)| limit.explains(signal))
+        .map(|&(limit, value)| RLimitError::new(limit, value, Reason::Killed(signal)))
+}
+
+#[cfg(not(unix))]
+pub(crate) fn hit(_limits: &[(RLimit, u64)], _status: ExitStatus) -> Option<RLimitError> {
+    None
+}
+
+#[cfg(unix)]
+fn signal_name(signal: i32) -> Option<&'static str> {
+    use libc;
+
+    let name = match signal {
+        libc::SIGXCPU => "SIGXCPU",
+        libc::SIGXFSZ => "SIGXFSZ",
+        libc::SIGSEGV => "SIGSEGV",
+        libc::SIGABRT => "SIGABRT",
+        libc::SIGKILL => "SIGKILL",
+        _ => return None,
+    };
+    Some(name)
+}
+
+#[cfg(not(unix))]
+fn signal_name(_signal: i32) -> Option<&'static str> {
+    None
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Reason {
+    #[cfg(not(unix))]
+    Unavailable,
+    Failed,
+    AboveHardLimit(u64),
+    Killed(i32),
+}
+
+#[derive(Debug)]
+pub struct RLimitError {
+    limit: RLimit,
+    value: u64,
+    reason: Reason,
+    cause: Option<failure::Error>,
+}
+
+impl RLimitError {
+    fn new(limit: RLimit, value: u64, reason: Reason) -> Self {
+        Self {
+            limit,
+            value,
+            reason,
+            cause: None,
+        }
+    }
+}
+
+impl failure::Fail for RLimitError {
+    fn cause(&self) -> Option<&dyn failure::Fail> {
+        self.cause.as_ref().map(failure::Error::as_fail)
+    }
+
+    fn backtrace(&self) -> Option<&failure::Backtrace> {
+        None
+    }
+}
+
+impl ChainFail for RLimitError {
+    fn chain<E>(mut self, error: E) -> Self
+    where
+        E: Into<failure::Error>,
+    {
+        self.cause = Some(error.into());
+        self
+    }
+}
+
+impl fmt::Display for RLimitError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = self.limit.name();
+        match self.reason {
+            #[cfg(not(unix))]
+            Reason::Unavailable => write!(f, "Can't set {}={} on this platform", name, self.value),
+            Reason::Failed => write!(f, "Can't set {}={}", name, self.value),
+            Reason::AboveHardLimit(hard) => write!(
+                f,
+                "Can't set {}={}, above the hard limit of {}",
+                name, self.value, hard
+            ),
+            Reason::Killed(signal) => {
+                match signal_name(signal) {
+                    Some(signal) => write!(f, "Command was killed by {}", signal)?,
+                    None => write!(f, "Command was killed by signal {}", signal)?,
+                }
+                write!(f, ", likely from hitting {}={}", name, self.value)
+            }
+        }
+    }
+}
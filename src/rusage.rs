@@ -0,0 +1,110 @@
+use std::io;
+use std::process::{Child, ExitStatus};
+use std::time::Duration;
+
+/// Resources a command used, as reported by the OS when it exited.
+///
+/// Only collected on Linux.  Includes descendants of the command that it waited for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ResourceUsage {
+    max_rss: u64,
+    user_time: Duration,
+    system_time: Duration,
+    minor_faults: u64,
+    major_faults: u64,
+}
+
+impl ResourceUsage {
+    /// Peak resident set size, in bytes.
+    pub fn max_rss(&self) -> u64 {
+        self.max_rss
+    }
+
+    /// CPU time spent in user mode.
+    pub fn user_time(&self) -> Duration {
+        self.user_time
+    }
+
+    /// CPU time spent in the kernel.
+    pub fn system_time(&self) -> Duration {
+        self.system_time
+    }
+
+    /// Total CPU time, user and system.
+    pub fn cpu_time(&self) -> Duration {
+        self.user_time + self.system_time
+    }
+
+    /// Page faults serviced without any I/O.
+    pub fn minor_faults(&self) -> u64 {
+        self.minor_faults
+    }
+
+    /// Page faults that required I/O.
+    pub fn major_faults(&self) -> u64 {
+        self.major_faults
+    }
+}
+
+/// Wait for the child, collecting its resource usage where supported.
+///
+/// Returns `None` if `block` is false and the child is still running.
+#[cfg(target_os = "linux")]
+pub(crate) fn wait(
+    child: &mut Child,
+    block: bool,
+) -> io::Result<Option<(ExitStatus, Option<ResourceUsage>)>> {
+    use libc;
+    use std::mem;
+    use std::os::unix::process::ExitStatusExt;
+
+    let pid = child.id() as libc::pid_t;
+    let options = if block { 0 } else { libc::WNOHANG };
+    let mut status = 0;
+    // Safety: `rusage` is plain data that `wait4` fills in.
+    let mut rusage: libc::rusage = unsafe { mem::zeroed() };
+    loop {
+        // Safety: both pointers are valid for the duration of the call.
+        let ret = unsafe { libc::wait4(pid, &mut status, options, &mut rusage) };
+        if ret == 0 {
+            return Ok(None);
+        }
+        if ret > 0 {
+            break;
+        }
+        let err = io::Error::last_os_error();
+        if err.kind() != io::ErrorKind::Interrupted {
+            return Err(err);
+        }
+    }
+
+    let usage = ResourceUsage {
+        max_rss: rusage.ru_maxrss as u64 * 1024,
+        user_time: timeval(rusage.ru_utime),
+        system_time: timeval(rusage.ru_stime),
+        minor_faults: rusage.ru_minflt as u64,
+        major_faults: rusage.ru_majflt as u64,
+    };
+    Ok(Some((ExitStatus::from_raw(status), Some(usage))))
+}
+
+#[cfg(target_os = "linux")]
+fn timeval(t: ::libc::timeval) -> Duration {
+    Duration::new(t.tv_sec as u64, t.tv_usec as u32 * 1000)
+}
+
+/// Wait for the child, collecting its resource usage where supported.
+///
+/// Returns `None` if `block` is false and the child is still running.
+#[cfg(not(target_os = "linux"))]
+pub(crate) fn wait(
+    child: &mut Child,
+    block: bool,
+) -> io::Result<Option<(ExitStatus, Option<ResourceUsage>)>> {
+    let status = if block {
+        Some(child.wait()?)
+    } else {
+        child.try_wait()?
+    };
+    Ok(status.map(|status| (status, None)))
+}
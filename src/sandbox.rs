@@ -0,0 +1,130 @@
+use std::ffi::OsString;
+use std::fs;
+use std::io;
+use std::path::{Component, PathBuf};
+
+use tempfile::{self, TempDir};
+
+/// Where a seeded file is placed in the sandbox.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Base {
+    Home,
+    Config,
+}
+
+/// A file to create in the sandbox before the command runs.
+#[derive(Debug, Clone)]
+pub(crate) struct Seed {
+    pub base: Base,
+    pub path: PathBuf,
+    pub contents: Vec<u8>,
+}
+
+/// Variables that are dropped, so locale and terminal settings of the developer don't leak in.
+const CLEARED: &[&str] = &[
+    "LANGUAGE",
+    "COLORTERM",
+    "COLUMNS",
+    "LINES",
+    "CLICOLOR_FORCE",
+];
+
+/// Variables that are set to deterministic values.
+const FIXED: &[(&str, &str)] = &[
+    ("LANG", "C"),
+    ("LC_ALL", "C"),
+    ("TZ", "UTC"),
+    ("TERM", "dumb"),
+];
+
+/// A throwaway HOME, XDG base directories and TMPDIR, removed when dropped.
+#[derive(Debug)]
+pub(crate) struct Sandbox {
+    root: TempDir,
+}
+
+impl Sandbox {
+    pub fn create(seeds: &[Seed]) -> io::Result<Self> {
+        let sandbox = Self {
+            root: tempfile::Builder::new().prefix("assert_cli-").tempdir()?,
+        };
+        for dir in &[
+            sandbox.home(),
+            sandbox.config_home(),
+            sandbox.cache_home(),
+            sandbox.data_home(),
+            sandbox.tmp(),
+        ] {
+            fs::create_dir_all(dir)?;
+        }
+        for seed in seeds {
+            let path = sandbox.seed_path(seed)?;
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::write(path, &seed.contents)?;
+        }
+        Ok(sandbox)
+    }
+
+    fn home(&self) -> PathBuf {
+        self.root.path().join("home")
+    }
+
+    fn config_home(&self) -> PathBuf {
+        self.home().join(".config")
+    }
+
+    fn cache_home(&self) -> PathBuf {
+        self.home().join(".cache")
+    }
+
+    fn data_home(&self) -> PathBuf {
+        self.home().join(".local").join("share")
+    }
+
+    fn tmp(&self) -> PathBuf {
+        self.root.path().join("tmp")
+    }
+
+    fn seed_path(&self, seed: &Seed) -> io::Result<PathBuf> {
+        let escapes = seed
+            .path
+            .components()
+            .any(|c| !matches!(c, Component::Normal(_) | Component::CurDir));
+        if escapes {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{:?} must be relative, without `..`", seed.path),
+            ));
+        }
+        let base = match seed.base {
+            Base::Home => self.home(),
+            Base::Config => self.config_home(),
+        };
+        Ok(base.join(&seed.path))
+    }
+
+    /// Point the environment at the sandbox, overriding variables that are already set.
+    pub fn isolate(&self, env: Vec<(OsString, OsString)>) -> Vec<(OsString, OsString)> {
+        let dirs = [
+            ("HOME", self.home()),
+            ("XDG_CONFIG_HOME", self.config_home()),
+            ("XDG_CACHE_HOME", self.cache_home()),
+            ("XDG_DATA_HOME", self.data_home()),
+            ("TMPDIR", self.tmp()),
+        ];
+        let overridden = |key: &OsString| {
+            let key = key.to_string_lossy();
+            key.starts_with("LC_")
+                || CLEARED.contains(&key.as_ref())
+                || FIXED.iter().any(|&(k, _)| k == key)
+                || dirs.iter().any(|&(k, _)| k == key)
+        };
+
+        let mut env: Vec<_> = env.into_iter().filter(|(k, _)| !overridden(k)).collect();
+        env.extend(FIXED.iter().map(|&(k, v)| (k.into(), v.into())));
+        env.extend(dirs.iter().map(|(k, v)| (k.into(), v.into())));
+        env
+    }
+}
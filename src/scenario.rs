@@ -0,0 +1,506 @@
+use std::collections::BTreeMap;
+use std::ffi::{OsStr, OsString};
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use environment::Environment;
+use failure;
+use regex::Regex;
+use serde_json::{self, Value};
+use tempfile;
+
+use assert::{Assert, Outcome};
+use template::interpolate;
+
+/// Ordered commands sharing a working directory, environment and variables.
+///
+/// Arguments and stdin of a step may refer to variables as `{name}`: those set with
+/// [`var`](#method.var), those captured from the output of earlier steps, and `{workdir}`.
+/// `{{` and `}}` are literal braces.
+///
+/// # Examples
+///
+/// ```rust
+/// extern crate assert_cli;
+/// use assert_cli::{Scenario, Step};
+///
+/// Scenario::new()
+///     .step("init", Step::command(&["sh", "-c", "echo 'id: 42' > state; cat state"])
+///         .capture("id", r"id: (\d+)"))
+///     .step("show", Step::command(&["cat", "state"])
+///         .expect(|a| a.stdout().is("id: 42")))
+///     .step("check", Step::command(&["grep", "{id}", "state"]))
+///     .unwrap();
+/// ```
+#[derive(Debug)]
+#[must_use]
+pub struct Scenario {
+    current_dir: Option<PathBuf>,
+    env: Environment,
+    vars: BTreeMap<String, String>,
+    steps: Vec<Step>,
+}
+
+impl Default for Scenario {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Scenario {
+    /// Create a scenario running in a fresh temporary directory.
+    pub fn new() -> Self {
+        Self {
+            current_dir: None,
+            env: Environment::inherit(),
+            vars: BTreeMap::new(),
+            steps: vec![],
+        }
+    }
+
+    /// Run the steps in `dir` instead of a temporary directory.
+    pub fn current_dir<P: Into<PathBuf>>(mut self, dir: P) -> Self {
+        self.current_dir = Some(dir.into());
+        self
+    }
+
+    /// Sets environment variables for all steps, see [`Assert::with_env`].
+    ///
+    /// [`Assert::with_env`]: struct.Assert.html#method.with_env
+    pub fn with_env<E: Into<Environment>>(mut self, env: E) -> Self {
+        self.env = env.into();
+        self
+    }
+
+    /// Set a variable for the steps to refer to.
+    pub fn var<N: Into<String>, V: Into<String>>(mut self, name: N, value: V) -> Self {
+        self.vars.insert(name.into(), value.into());
+        self
+    }
+
+    /// Add a step, run after the ones added before.
+    pub fn step<N: Into<String>>(mut self, name: N, mut step: Step) -> Self {
+        step.name = name.into();
+        self.steps.push(step);
+        self
+    }
+
+    /// Run the steps in order, stopping at the first that fails.
+    pub fn run(self) -> Result<Transcript, ScenarioError> {
+        let mut transcript = Transcript {
+            steps: vec![],
+            vars: self.vars,
+        };
+        let tmpdir;
+        let dir = match self.current_dir {
+            Some(dir) => dir,
+            None => {
+                tmpdir = tempfile::Builder::new()
+                    .prefix("assert_cli-")
+                    .tempdir()
+                    .map_err(|e| ScenarioError::new(0, "setup", &transcript, e))?;
+                tmpdir.path().to_owned()
+            }
+        };
+        transcript
+            .vars
+            .insert("workdir".to_owned(), dir.to_string_lossy().into_owned());
+
+        for (i, step) in self.steps.into_iter().enumerate() {
+            let name = step.name.clone();
+            let fail = |transcript: &Transcript, cause| {
+                ScenarioError::new(i + 1, &name, transcript, cause)
+            };
+            let record = step
+                .run(&transcript.vars, &self.env, &dir)
+                .map_err(|cause| fail(&transcript, cause))?;
+            transcript.vars.extend(record.captured.clone());
+            transcript.steps.push(record);
+        }
+        Ok(transcript)
+    }
+
+    /// Run the steps, panicking with the failing step and the transcript of earlier ones.
+    pub fn unwrap(self) -> Transcript {
+        match self.run() {
+            Ok(transcript) => transcript,
+            Err(err) => {
+                let chain = (&err as &dyn failure::Fail).iter_chain();
+                panic!("{}", Assert::format_causes(chain));
+            }
+        }
+    }
+}
+
+enum Capture {
+    Regex(String, String),
+    Json(String, String),
+}
+
+/// A command run as part of a [`Scenario`](struct.Scenario.html).
+///
+/// Defaults to asserting _successful_ execution.
+#[must_use]
+pub struct Step {
+    name: String,
+    cmd: Vec<OsString>,
+    stdin: Option<String>,
+    captures: Vec<Capture>,
+    expect: Option<Box<dyn FnOnce(Assert) -> Assert + Send>>,
+}
+
+impl Step {
+    /// Run a custom command.
+    pub fn command<S: AsRef<OsStr>>(cmd: &[S]) -> Self {
+        Self::from_assert(&Assert::command(cmd))
+    }
+
+    /// Run the crate's main binary.
+    pub fn main_binary() -> Self {
+        Self::from_assert(&Assert::main_binary())
+    }
+
+    /// Run a specific binary of the current crate.
+    pub fn cargo_binary<S: AsRef<OsStr>>(name: S) -> Self {
+        Self::from_assert(&Assert::cargo_binary(name))
+    }
+
+    fn from_assert(assert: &Assert) -> Self {
+        Self {
+            name: String::new(),
+            cmd: assert.cmd().to_vec(),
+            stdin: None,
+            captures: vec![],
+            expect: None,
+        }
+    }
+
+    /// Add arguments to the command.
+    pub fn with_args<S: AsRef<OsStr>>(mut self, args: &[S]) -> Self {
+        self.cmd.extend(args.iter().map(OsString::from));
+        self
+    }
+
+    /// Add stdin to the command.
+    pub fn stdin<S: Into<String>>(mut self, contents: S) -> Self {
+        self.stdin = Some(contents.into());
+        self
+    }
+
+    /// Store what the first group of `pattern` matches in stdout as variable `name`, or the
+    /// whole match if it has no groups.
+    pub fn capture<N: Into<String>, P: Into<String>>(mut self, name: N, pattern: P) -> Self {
+        self.captures
+            .push(Capture::Regex(name.into(), pattern.into()));
+        self
+    }
+
+    /// Parse stdout as JSON and store the value at `path` as variable `name`.
+    ///
+    /// `path` is either a JSON pointer like `/items/0/id`, or dotted like `items.0.id`.  Strings
+    /// are stored without quotes, other values as JSON.
+    pub fn capture_json<N: Into<String>, P: Into<String>>(mut self, name: N, path: P) -> Self {
+        self.captures.push(Capture::Json(name.into(), path.into()));
+        self
+    }
+
+    /// Add assertions to the step's `Assert`.
+    pub fn expect<F>(mut self, assertions: F) -> Self
+    where
+        F: FnOnce(Assert) -> Assert + Send + 'static,
+    {
+        self.expect = Some(Box::new(assertions));
+        self
+    }
+
+    fn run(
+        self,
+        vars: &BTreeMap<String, String>,
+        env: &Environment,
+        dir: &Path,
+    ) -> Result<StepRecord, failure::Error> {
+        let resolve = |placeholder: &str| match vars.get(placeholder) {
+            Some(value) => Ok(Some(OsString::from(value))),
+            None => Err(format!("unknown variable `{{{}}}`", placeholder)),
+        };
+        let mut cmd = vec![];
+        for arg in &self.cmd {
+            let arg = match arg.to_str() {
+                Some(arg) => interpolate(arg, resolve).map_err(failure::err_msg)?,
+                None => arg.clone(),
+            };
+            cmd.push(arg);
+        }
+
+        let mut assert = Assert::command(&cmd).with_env(env).current_dir(dir);
+        if let Some(ref stdin) = self.stdin {
+            let stdin = interpolate(stdin, resolve).map_err(failure::err_msg)?;
+            assert = assert.stdin(stdin.to_string_lossy().into_owned());
+        }
+        if let Some(expect) = self.expect {
+            assert = expect(assert);
+        }
+        let outcome = assert.execute()?;
+
+        let mut captured = BTreeMap::new();
+        for capture in &self.captures {
+            let (name, value) = capture.apply(&outcome)?;
+            captured.insert(name, value);
+        }
+        let cmd: Vec<_> = cmd.iter().map(|arg| arg.to_string_lossy()).collect();
+        Ok(StepRecord {
+            name: self.name,
+            cmd: cmd.join(" "),
+            code: outcome.code(),
+            stdout: String::from_utf8_lossy(outcome.stdout()).into_owned(),
+            stderr: String::from_utf8_lossy(outcome.stderr()).into_owned(),
+            captured,
+        })
+    }
+}
+
+impl fmt::Debug for Step {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Step")
+            .field("name", &self.name)
+            .field("cmd", &self.cmd)
+            .field("stdin", &self.stdin)
+            .finish()
+    }
+}
+
+impl Capture {
+    fn apply(&self, outcome: &Outcome) -> Result<(String, String), failure::Error> {
+        let stdout = String::from_utf8_lossy(outcome.stdout());
+        match *self {
+            Capture::Regex(ref name, ref pattern) => {
+                let regex = Regex::new(pattern)?;
+                let captures = regex.captures(&stdout).ok_or_else(|| {
+                    failure::err_msg(format!(
+                        "`{}` didn't match stdout=```{}```",
+                        pattern, stdout
+                    ))
+                })?;
+                let value = captures.get(1).or_else(|| captures.get(0));
+                let value = value.map_or("", |m| m.as_str()).to_owned();
+                Ok((name.clone(), value))
+            }
+            Capture::Json(ref name, ref path) => {
+                let json: Value = serde_json::from_str(&stdout)?;
+                let pointer = if path.starts_with('/') {
+                    path.clone()
+                } else {
+                    path.split('.')
+                        .map(|key| format!("/{}", key.replace('~', "~0").replace('/', "~1")))
+                        .collect()
+                };
+                let value = json.pointer(&pointer).ok_or_else(|| {
+                    failure::err_msg(format!("`{}` not found in stdout=```{}```", path, stdout))
+                })?;
+                let value = match *value {
+                    Value::String(ref s) => s.clone(),
+                    ref value => value.to_string(),
+                };
+                Ok((name.clone(), value))
+            }
+        }
+    }
+}
+
+/// What a step of a [`Scenario`](struct.Scenario.html) ran and printed.
+#[derive(Debug, Clone)]
+pub struct StepRecord {
+    name: String,
+    cmd: String,
+    code: Option<i32>,
+    stdout: String,
+    stderr: String,
+    captured: BTreeMap<String, String>,
+}
+
+impl StepRecord {
+    /// The step's name.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The command, after substituting variables.
+    pub fn cmd(&self) -> &str {
+        &self.cmd
+    }
+
+    /// The exit code, `None` if the command was killed by a signal.
+    pub fn code(&self) -> Option<i32> {
+        self.code
+    }
+
+    /// What the command wrote to stdout.
+    pub fn stdout(&self) -> &str {
+        &self.stdout
+    }
+
+    /// What the command wrote to stderr.
+    pub fn stderr(&self) -> &str {
+        &self.stderr
+    }
+
+    /// The variables captured from the output.
+    pub fn captured(&self) -> &BTreeMap<String, String> {
+        &self.captured
+    }
+}
+
+impl fmt::Display for StepRecord {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{}: `{}`", self.name, self.cmd)?;
+        writeln!(f, "exit code: {:?}", self.code)?;
+        writeln!(f, "stdout=```{}```", self.stdout)?;
+        write!(f, "stderr=```{}```", self.stderr)
+    }
+}
+
+/// The steps a [`Scenario`](struct.Scenario.html) ran, and the variables they left behind.
+#[derive(Debug, Clone)]
+pub struct Transcript {
+    steps: Vec<StepRecord>,
+    vars: BTreeMap<String, String>,
+}
+
+impl Transcript {
+    /// The steps that passed, in order.
+    pub fn steps(&self) -> &[StepRecord] {
+        &self.steps
+    }
+
+    /// The value of a variable.
+    pub fn var(&self, name: &str) -> Option<&str> {
+        self.vars.get(name).map(String::as_str)
+    }
+}
+
+impl fmt::Display for Transcript {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, step) in self.steps.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "[{}] {}", i + 1, step)?;
+        }
+        Ok(())
+    }
+}
+
+/// A failed step of a [`Scenario`](struct.Scenario.html).
+#[derive(Debug)]
+pub struct ScenarioError {
+    step: usize,
+    name: String,
+    transcript: Transcript,
+    cause: failure::Error,
+}
+
+impl ScenarioError {
+    fn new<E: Into<failure::Error>>(
+        step: usize,
+        name: &str,
+        transcript: &Transcript,
+        cause: E,
+    ) -> Self {
+        Self {
+            step,
+            name: name.to_owned(),
+            transcript: transcript.clone(),
+            cause: cause.into(),
+        }
+    }
+
+    /// The number of the failed step, counting from 1.
+    pub fn step(&self) -> usize {
+        self.step
+    }
+
+    /// The steps before the failed one.
+    pub fn transcript(&self) -> &Transcript {
+        &self.transcript
+    }
+}
+
+impl failure::Fail for ScenarioError {
+    fn cause(&self) -> Option<&dyn failure::Fail> {
+        Some(self.cause.as_fail())
+    }
+
+    fn backtrace(&self) -> Option<&failure::Backtrace> {
+        None
+    }
+}
+
+impl fmt::Display for ScenarioError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Step {} `{}` failed", self.step, self.name)?;
+        if !self.transcript.steps.is_empty() {
+            write!(f, " after:\n{}", self.transcript)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn captures_feed_later_steps() {
+        let transcript = Scenario::new()
+            .var("greeting", "hello")
+            .step(
+                "json",
+                Step::command(&["echo", r#"{{"items": [{{"id": "a/1"}}], "n": 2}}"#])
+                    .capture_json("id", "items.0.id")
+                    .capture_json("n", "/n"),
+            )
+            .step(
+                "write",
+                Step::command(&["sh", "-c", "cat > \"$0\""])
+                    .with_args(&["{workdir}/out"])
+                    .stdin("{greeting} {id} {n}"),
+            )
+            .step(
+                "read",
+                Step::command(&["cat", "out"]).expect(|a| a.stdout().is("hello a/1 2")),
+            )
+            .unwrap();
+        assert_eq!(transcript.var("id"), Some("a/1"));
+        assert_eq!(transcript.steps()[2].stdout(), "hello a/1 2");
+    }
+
+    #[test]
+    fn failure_reports_step_and_transcript() {
+        let err = Scenario::new()
+            .step(
+                "init",
+                Step::command(&["echo", "version 3"]).capture("v", r"\d+"),
+            )
+            .step("check", Step::command(&["test", "{v}", "=", "4"]))
+            .run()
+            .unwrap_err();
+        assert_eq!(err.step(), 2);
+        let message = err.to_string();
+        assert!(
+            message.starts_with("Step 2 `check` failed after:\n[1] init: `echo version 3`"),
+            "{}",
+            message
+        );
+        assert!(message.contains("stdout=```version 3\n```"), "{}", message);
+    }
+
+    #[test]
+    fn unknown_variable_fails_step() {
+        let err = Scenario::new()
+            .step("echo", Step::command(&["echo", "{missing}"]))
+            .run()
+            .unwrap_err();
+        let cause = failure::Fail::cause(&err).unwrap().to_string();
+        assert_eq!(cause, "unknown variable `{missing}`");
+    }
+}
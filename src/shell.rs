@@ -0,0 +1,275 @@
+use std::ffi::{OsStr, OsString};
+use std::fmt;
+use std::iter::Peekable;
+use std::mem;
+use std::str::CharIndices;
+
+use failure;
+
+/// Why a command string couldn't be split into words.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Reason {
+    Unbalanced(char),
+    TrailingBackslash,
+    BadSubstitution,
+    Unsupported(char),
+    Empty,
+}
+
+/// A command string that isn't a simple POSIX shell command, see
+/// [`Assert::parse_command`](struct.Assert.html#method.parse_command).
+#[derive(Debug)]
+pub struct ParseCommandError {
+    command: String,
+    position: usize,
+    reason: Reason,
+}
+
+impl ParseCommandError {
+    fn new(command: &str, position: usize, reason: Reason) -> Self {
+        Self {
+            command: command.to_owned(),
+            position,
+            reason,
+        }
+    }
+
+    /// The byte offset into the command string where parsing failed.
+    pub fn position(&self) -> usize {
+        self.position
+    }
+}
+
+impl failure::Fail for ParseCommandError {
+    fn cause(&self) -> Option<&dyn failure::Fail> {
+        None
+    }
+
+    fn backtrace(&self) -> Option<&failure::Backtrace> {
+        None
+    }
+}
+
+impl fmt::Display for ParseCommandError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Can't parse command `{}`: ", self.command)?;
+        match self.reason {
+            Reason::Unbalanced(quote) => {
+                write!(f, "unbalanced `{}` at byte {}", quote, self.position)
+            }
+            Reason::TrailingBackslash => write!(f, "trailing backslash"),
+            Reason::BadSubstitution => write!(f, "bad substitution at byte {}", self.position),
+            Reason::Unsupported(c) => write!(
+                f,
+                "`{}` at byte {} needs a shell, quote it to pass it as an argument",
+                c, self.position
+            ),
+            Reason::Empty => write!(f, "no program given"),
+        }
+    }
+}
+
+/// Split `command` into words like a POSIX shell, without running one.
+///
+/// Supports single and double quotes, backslash escapes, comments and `$NAME`/`${NAME}` looked
+/// up in `env`, unset variables being empty.  Expanded values are not split any further.
+/// Pipes, redirections, command substitution and the like are rejected.
+pub(crate) fn split(
+    command: &str,
+    env: &[(OsString, OsString)],
+) -> Result<Vec<OsString>, ParseCommandError> {
+    let mut words = vec![];
+    let mut word = OsString::new();
+    // Whether there is a word, even if empty like `''`.
+    let mut in_word = false;
+    let mut chars = command.char_indices().peekable();
+    let error = |position, reason| ParseCommandError::new(command, position, reason);
+
+    while let Some((i, c)) = chars.next() {
+        match c {
+            ' ' | '\t' | '\n' => {
+                if in_word {
+                    words.push(mem::replace(&mut word, OsString::new()));
+                    in_word = false;
+                }
+            }
+            '#' if !in_word => {
+                while chars.peek().is_some_and(|&(_, c)| c != '\n') {
+                    chars.next();
+                }
+            }
+            '\\' => match chars.next() {
+                Some((_, '\n')) => {}
+                Some((_, c)) => {
+                    word.push(c.encode_utf8(&mut [0; 4]));
+                    in_word = true;
+                }
+                None => return Err(error(i, Reason::TrailingBackslash)),
+            },
+            '\'' => {
+                in_word = true;
+                loop {
+                    match chars.next() {
+                        Some((_, '\'')) => break,
+                        Some((_, c)) => word.push(c.encode_utf8(&mut [0; 4])),
+                        None => return Err(error(i, Reason::Unbalanced('\''))),
+                    }
+                }
+            }
+            '"' => {
+                in_word = true;
+                loop {
+                    match chars.next() {
+                        Some((_, '"')) => break,
+                        Some((_, '\\')) => match chars.peek().cloned() {
+                            Some((_, '\n')) => {
+                                chars.next();
+                            }
+                            Some((_, c @ '$')) | Some((_, c @ '`')) | Some((_, c @ '"'))
+                            | Some((_, c @ '\\')) => {
+                                chars.next();
+                                word.push(c.encode_utf8(&mut [0; 4]));
+                            }
+                            _ => word.push("\\"),
+                        },
+                        Some((j, '$')) => {
+                            let value = expand(&mut chars, env).map_err(|r| error(j, r))?;
+                            word.push(value.unwrap_or_else(|| OsStr::new("$")));
+                        }
+                        Some((j, '`')) => return Err(error(j, Reason::Unsupported('`'))),
+                        Some((_, c)) => word.push(c.encode_utf8(&mut [0; 4])),
+                        None => return Err(error(i, Reason::Unbalanced('"'))),
+                    }
+                }
+            }
+            '$' => match expand(&mut chars, env).map_err(|r| error(i, r))? {
+                Some(value) => {
+                    word.push(value);
+                    // Like a shell, drop words that only consist of an empty expansion.
+                    in_word = in_word || !word.is_empty();
+                }
+                None => {
+                    word.push("$");
+                    in_word = true;
+                }
+            },
+            '|' | '&' | ';' | '<' | '>' | '(' | ')' | '`' => {
+                return Err(error(i, Reason::Unsupported(c)))
+            }
+            c => {
+                word.push(c.encode_utf8(&mut [0; 4]));
+                in_word = true;
+            }
+        }
+    }
+    if in_word {
+        words.push(word);
+    }
+    if words.is_empty() {
+        return Err(error(0, Reason::Empty));
+    }
+    Ok(words)
+}
+
+/// Expand the variable after a `$`, `None` if there is none and the `$` is literal.
+fn expand<'e>(
+    chars: &mut Peekable<CharIndices>,
+    env: &'e [(OsString, OsString)],
+) -> Result<Option<&'e OsStr>, Reason> {
+    let braced = match chars.peek() {
+        Some(&(_, '{')) => {
+            chars.next();
+            true
+        }
+        Some(&(_, '(')) => return Err(Reason::Unsupported('(')),
+        Some(&(_, c)) if c == '_' || c.is_ascii_alphabetic() => false,
+        _ => return Ok(None),
+    };
+
+    let mut name = String::new();
+    while let Some(&(_, c)) = chars.peek() {
+        if c != '_' && !c.is_ascii_alphanumeric() {
+            break;
+        }
+        name.push(c);
+        chars.next();
+    }
+    if braced {
+        match chars.next() {
+            Some((_, '}')) if !name.is_empty() => {}
+            _ => return Err(Reason::BadSubstitution),
+        }
+    }
+
+    let value = env
+        .iter()
+        .rev()
+        .find(|(key, _)| *key == *name)
+        .map_or(OsStr::new(""), |(_, value)| value.as_os_str());
+    Ok(Some(value))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn words(command: &str) -> Vec<String> {
+        let env = vec![
+            (OsString::from("NAME"), OsString::from("John Doe")),
+            (OsString::from("EMPTY"), OsString::new()),
+        ];
+        split(command, &env)
+            .unwrap()
+            .into_iter()
+            .map(|w| w.into_string().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn quotes_and_escapes() {
+        assert_eq!(
+            words(r#"mytool --name "John Doe" --flag='a b' a\ b "\$x\y" '' # comment"#),
+            [
+                "mytool",
+                "--name",
+                "John Doe",
+                "--flag=a b",
+                "a b",
+                r"$x\y",
+                ""
+            ]
+        );
+        assert_eq!(words("a \\\n  b"), ["a", "b"]);
+    }
+
+    #[test]
+    fn variables() {
+        assert_eq!(
+            words(r#"echo $NAME "${NAME}s" '$NAME' $EMPTY "$EMPTY" $ x$UNSET"#),
+            ["echo", "John Doe", "John Does", "$NAME", "", "$", "x"]
+        );
+    }
+
+    #[test]
+    fn errors() {
+        let error = |command| split(command, &[]).unwrap_err().to_string();
+        assert_eq!(
+            error("echo 'a b"),
+            "Can't parse command `echo 'a b`: unbalanced `'` at byte 5"
+        );
+        assert_eq!(
+            error(r#"echo "a"#),
+            "Can't parse command `echo \"a`: unbalanced `\"` at byte 5"
+        );
+        assert_eq!(
+            error("echo a | wc"),
+            "Can't parse command `echo a | wc`: `|` at byte 7 needs a shell, quote it to pass it \
+             as an argument"
+        );
+        assert_eq!(
+            error("echo ${A"),
+            "Can't parse command `echo ${A`: bad substitution at byte 5"
+        );
+        assert_eq!(error("  "), "Can't parse command `  `: no program given");
+    }
+}
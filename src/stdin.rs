@@ -0,0 +1,155 @@
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::mem;
+use std::path::{Path, PathBuf};
+use std::process::{ChildStdin, Stdio};
+use std::sync::Arc;
+use std::thread;
+
+pub(crate) type Generator = Arc<dyn Fn(&mut dyn Write) -> io::Result<()> + Send + Sync>;
+
+/// Where the command's stdin comes from.
+#[derive(Default)]
+pub(crate) enum Stdin {
+    /// A pipe that is closed right away.
+    #[default]
+    Closed,
+    Inherit,
+    Null,
+    Bytes(Vec<u8>),
+    File(PathBuf),
+    Reader(Box<dyn Read + Send>),
+    Generator(Generator),
+}
+
+impl Stdin {
+    pub fn stdio(&self) -> io::Result<Stdio> {
+        let stdio = match *self {
+            Stdin::Inherit => Stdio::inherit(),
+            Stdin::Null => Stdio::null(),
+            Stdin::File(ref path) => Stdio::from(File::open(path)?),
+            Stdin::Closed | Stdin::Bytes(_) | Stdin::Reader(_) | Stdin::Generator(_) => {
+                Stdio::piped()
+            }
+        };
+        Ok(stdio)
+    }
+
+    /// Read a reader into memory, so the input can be fed to more than one run.
+    pub fn buffer(&mut self) -> io::Result<()> {
+        if let Stdin::Reader(ref mut reader) = *self {
+            let mut contents = vec![];
+            reader.read_to_end(&mut contents)?;
+            *self = Stdin::Bytes(contents);
+        }
+        Ok(())
+    }
+
+    /// The input for a run: a copy, unless it comes from a reader, which only the first run
+    /// gets to read.
+    pub fn replay(&mut self) -> Stdin {
+        match *self {
+            Stdin::Closed => Stdin::Closed,
+            Stdin::Inherit => Stdin::Inherit,
+            Stdin::Null => Stdin::Null,
+            Stdin::Bytes(ref contents) => Stdin::Bytes(contents.clone()),
+            Stdin::File(ref path) => Stdin::File(path.clone()),
+            Stdin::Generator(ref generate) => Stdin::Generator(Arc::clone(generate)),
+            Stdin::Reader(_) => mem::replace(self, Stdin::Closed),
+        }
+    }
+
+    /// A relative file resolved against `dir`, `None` when that changes nothing.
+    pub fn relative_to(&self, dir: Option<&Path>) -> Option<Stdin> {
+        match *self {
+            Stdin::File(ref path) if path.is_relative() => {
+                dir.map(|dir| Stdin::File(dir.join(path)))
+            }
+            _ => None,
+        }
+    }
+
+    /// How many bytes the command will be given, if known up front.
+    pub fn len(&self) -> Option<u64> {
+        match *self {
+            Stdin::Closed | Stdin::Null => Some(0),
+            Stdin::Bytes(ref contents) => Some(contents.len() as u64),
+            Stdin::File(ref path) => fs::metadata(path).ok().map(|m| m.len()),
+            Stdin::Inherit | Stdin::Reader(_) | Stdin::Generator(_) => None,
+        }
+    }
+
+    /// Where the input comes from, for humans.
+    pub fn describe(&self) -> String {
+        let source = match *self {
+            Stdin::Closed => "closed".to_owned(),
+            Stdin::Inherit => "inherited".to_owned(),
+            Stdin::Null => "null".to_owned(),
+            Stdin::Bytes(_) => "bytes".to_owned(),
+            Stdin::File(ref path) => format!("file {}", path.display()),
+            Stdin::Reader(_) => "reader".to_owned(),
+            Stdin::Generator(_) => "generator".to_owned(),
+        };
+        match self.len() {
+            Some(len) => format!("{} ({} bytes)", source, len),
+            None => source,
+        }
+    }
+
+    /// Write the contents on a separate thread, so the child can't block on a full stdout pipe
+    /// while we are still writing to its stdin.
+    pub fn feed(self, pipe: Option<ChildStdin>) -> Feeder {
+        let mut pipe = match pipe {
+            Some(pipe) => pipe,
+            None => return Feeder(None),
+        };
+
+        let handle = match self {
+            Stdin::Bytes(contents) => thread::spawn(move || pipe.write_all(&contents)),
+            Stdin::Reader(mut reader) => {
+                thread::spawn(move || io::copy(&mut reader, &mut pipe).map(|_| ()))
+            }
+            Stdin::Generator(generate) => thread::spawn(move || generate(&mut pipe)),
+            // Dropping the pipe closes it.
+            _ => return Feeder(None),
+        };
+        Feeder(Some(handle))
+    }
+}
+
+impl fmt::Debug for Stdin {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Stdin::Closed => write!(f, "Closed"),
+            Stdin::Inherit => write!(f, "Inherit"),
+            Stdin::Null => write!(f, "Null"),
+            Stdin::Bytes(ref contents) => write!(f, "Bytes({} bytes)", contents.len()),
+            Stdin::File(ref path) => write!(f, "File({:?})", path),
+            Stdin::Reader(_) => write!(f, "Reader"),
+            Stdin::Generator(_) => write!(f, "Generator"),
+        }
+    }
+}
+
+/// Handle on the thread writing to the child's stdin.
+pub(crate) struct Feeder(Option<thread::JoinHandle<io::Result<()>>>);
+
+impl Feeder {
+    /// Wait for the writer to finish.
+    ///
+    /// A child exiting without reading all of its input is not an error.
+    pub fn join(self) -> io::Result<()> {
+        let handle = match self.0 {
+            Some(handle) => handle,
+            None => return Ok(()),
+        };
+        let result = handle
+            .join()
+            .unwrap_or_else(|_| Err(io::Error::other("stdin writer panicked")));
+        match result {
+            Err(ref e) if e.kind() == io::ErrorKind::BrokenPipe => Ok(()),
+            result => result,
+        }
+    }
+}
@@ -0,0 +1,405 @@
+use std::env;
+use std::ffi::{OsStr, OsString};
+use std::path::{Path, PathBuf};
+
+use environment::Environment;
+use failure;
+use tempfile::{self, TempDir};
+
+use assert::Assert;
+use rlimit::RLimit;
+use sandbox::{Base, Seed};
+
+/// A command shared by many test cases, from which each case derives an `Assert`.
+///
+/// Arguments and the working directory may contain placeholders, which are replaced when an
+/// `Assert` is derived:
+///
+/// - `{tmpdir}`: a fresh temporary directory, removed once the `Assert` is dropped.
+/// - `{fixture:name}`: `name` resolved in the fixtures directory, see
+///   [`fixtures`](#method.fixtures).
+/// - `{{` and `}}`: literal braces.
+///
+/// Other braces, like in `{"a": 1}` or `awk '{print $1}'`, are left as they are.
+///
+/// Cases extend the template's environment: [`Assert::with_env`] on a derived `Assert` adds
+/// variables to those of [`with_env`](#method.with_env), overriding any of the same name.
+///
+/// # Examples
+///
+/// ```rust
+/// extern crate assert_cli;
+/// use assert_cli::CommandTemplate;
+///
+/// let template = CommandTemplate::command(&["cat"])
+///     .fixtures("src")
+///     .with_env(&[("LC_ALL", "C")]);
+///
+/// template.case(&["{fixture:lib.rs}"])
+///     .stdout().contains("assert_cli")
+///     .unwrap();
+/// template.case(&["{tmpdir}/missing"])
+///     .fails()
+///     .unwrap();
+/// template.case(&["-"])
+///     .with_env(&[("LC_ALL", "en_US.UTF-8")])
+///     .stdin("{}")
+///     .stdout().is("{}")
+///     .unwrap();
+/// ```
+///
+/// [`Assert::with_env`]: struct.Assert.html#method.with_env
+#[derive(Debug, Clone)]
+pub struct CommandTemplate {
+    cmd: Vec<OsString>,
+    env: Environment,
+    current_dir: Option<PathBuf>,
+    hermetic: bool,
+    seeds: Vec<Seed>,
+    rlimits: Vec<(RLimit, u64)>,
+    fixtures: Option<PathBuf>,
+}
+
+impl CommandTemplate {
+    /// Run the crate's main binary.
+    pub fn main_binary() -> Self {
+        Self::from_assert(&Assert::main_binary())
+    }
+
+    /// Run a specific binary of the current crate.
+    pub fn cargo_binary<S: AsRef<OsStr>>(name: S) -> Self {
+        Self::from_assert(&Assert::cargo_binary(name))
+    }
+
+    /// Run a specific example of the current crate.
+    pub fn example<S: AsRef<OsStr>>(name: S) -> Self {
+        Self::from_assert(&Assert::example(name))
+    }
+
+    /// Run a custom command.
+    pub fn command<S: AsRef<OsStr>>(cmd: &[S]) -> Self {
+        Self::from_assert(&Assert::command(cmd))
+    }
+
+    fn from_assert(assert: &Assert) -> Self {
+        Self {
+            cmd: assert.cmd().to_vec(),
+            env: Environment::inherit(),
+            current_dir: None,
+            hermetic: false,
+            seeds: vec![],
+            rlimits: vec![],
+            fixtures: None,
+        }
+    }
+
+    /// Add arguments shared by all cases.
+    pub fn with_args<S: AsRef<OsStr>>(mut self, args: &[S]) -> Self {
+        self.cmd.extend(args.iter().map(OsString::from));
+        self
+    }
+
+    /// Sets environment variables for all cases, see [`Assert::with_env`].
+    ///
+    /// [`Assert::with_env`]: struct.Assert.html#method.with_env
+    pub fn with_env<E: Into<Environment>>(mut self, env: E) -> Self {
+        self.env = env.into();
+        self
+    }
+
+    /// Sets the working directory for all cases.
+    pub fn current_dir<P: Into<PathBuf>>(mut self, dir: P) -> Self {
+        self.current_dir = Some(dir.into());
+        self
+    }
+
+    /// Run all cases in a sandbox, see [`Assert::hermetic`].
+    ///
+    /// [`Assert::hermetic`]: struct.Assert.html#method.hermetic
+    pub fn hermetic(mut self) -> Self {
+        self.hermetic = true;
+        self
+    }
+
+    /// Create a file relative to the sandboxed `HOME`, see [`Assert::home_file`].
+    ///
+    /// [`Assert::home_file`]: struct.Assert.html#method.home_file
+    pub fn home_file<P: Into<PathBuf>, C: Into<Vec<u8>>>(mut self, path: P, contents: C) -> Self {
+        self.seeds.push(Seed {
+            base: Base::Home,
+            path: path.into(),
+            contents: contents.into(),
+        });
+        self.hermetic()
+    }
+
+    /// Create a file relative to the sandboxed `XDG_CONFIG_HOME`, see [`Assert::config_file`].
+    ///
+    /// [`Assert::config_file`]: struct.Assert.html#method.config_file
+    pub fn config_file<P: Into<PathBuf>, C: Into<Vec<u8>>>(mut self, path: P, contents: C) -> Self {
+        self.seeds.push(Seed {
+            base: Base::Config,
+            path: path.into(),
+            contents: contents.into(),
+        });
+        self.hermetic()
+    }
+
+    /// Set a resource limit for all cases, see [`Assert::rlimit`].
+    ///
+    /// [`Assert::rlimit`]: struct.Assert.html#method.rlimit
+    pub fn rlimit(mut self, resource: RLimit, value: u64) -> Self {
+        self.rlimits.retain(|&(r, _)| r != resource);
+        self.rlimits.push((resource, value));
+        self
+    }
+
+    /// Directory `{fixture:name}` is resolved in.
+    ///
+    /// Defaults to `tests/fixtures` in the crate being tested.
+    pub fn fixtures<P: Into<PathBuf>>(mut self, dir: P) -> Self {
+        self.fixtures = Some(dir.into());
+        self
+    }
+
+    /// Derive an `Assert` for a case.
+    ///
+    /// # Errors
+    ///
+    /// If an argument names a fixture that doesn't exist, or the temporary directory can't be
+    /// created, executing the `Assert` fails.
+    pub fn assert(&self) -> Assert {
+        self.case::<&str>(&[])
+    }
+
+    /// Derive an `Assert` for a case, with extra arguments.
+    ///
+    /// # Errors
+    ///
+    /// See [`assert`](#method.assert).
+    pub fn case<S: AsRef<OsStr>>(&self, args: &[S]) -> Assert {
+        let mut substitution = Substitution {
+            fixtures: self.fixtures.clone().unwrap_or_else(default_fixtures),
+            tmpdir: None,
+        };
+        let mut error = None;
+        let mut apply = |arg: &OsStr| {
+            substitution.apply(arg).unwrap_or_else(|err| {
+                error.get_or_insert(err);
+                arg.to_owned()
+            })
+        };
+        let cmd: Vec<OsString> = self
+            .cmd
+            .iter()
+            .map(OsStr::new)
+            .chain(args.iter().map(AsRef::as_ref))
+            .map(&mut apply)
+            .collect();
+        let current_dir = self
+            .current_dir
+            .as_ref()
+            .map(|dir| PathBuf::from(apply(dir.as_os_str())));
+
+        let mut assert = Assert::command(&cmd).extend_env(&self.env);
+        if let Some(err) = error {
+            assert = assert.setup_failed(failure::err_msg(err));
+        }
+        if let Some(dir) = current_dir {
+            assert = assert.current_dir(dir);
+        }
+        if self.hermetic {
+            assert = assert.hermetic();
+        }
+        for seed in &self.seeds {
+            assert = match seed.base {
+                Base::Home => assert.home_file(seed.path.clone(), seed.contents.clone()),
+                Base::Config => assert.config_file(seed.path.clone(), seed.contents.clone()),
+            };
+        }
+        for &(resource, value) in &self.rlimits {
+            assert = assert.rlimit(resource, value);
+        }
+        match substitution.tmpdir {
+            Some(tmpdir) => assert.keep_alive(tmpdir),
+            None => assert,
+        }
+    }
+}
+
+fn default_fixtures() -> PathBuf {
+    let root = env::var_os("CARGO_MANIFEST_DIR").unwrap_or_default();
+    Path::new(&root).join("tests").join("fixtures")
+}
+
+/// Replace each `{placeholder}` in `text` with what `resolve` returns for it, and `{{`, `}}` with
+/// literal braces.
+///
+/// Braces `resolve` returns `None` for, or that aren't closed, are kept as they are.  A `}}`
+/// closes such kept braces before it stands for a literal brace, so nested JSON like
+/// `{"a":{"b":1}}` is kept whole.
+pub(crate) fn interpolate<F>(text: &str, mut resolve: F) -> Result<OsString, String>
+where
+    F: FnMut(&str) -> Result<Option<OsString>, String>,
+{
+    let mut result = OsString::new();
+    let mut rest = text;
+    let mut kept = 0;
+    while let Some(start) = rest.find(['{', '}']) {
+        result.push(&rest[..start]);
+        let tail = &rest[start..];
+        if tail.starts_with('}') && kept > 0 {
+            kept -= 1;
+            result.push("}");
+            rest = &tail[1..];
+            continue;
+        }
+        if tail.starts_with("{{") || tail.starts_with("}}") {
+            result.push(&tail[..1]);
+            rest = &tail[2..];
+            continue;
+        }
+        let resolved = match tail.find('}') {
+            Some(end) if tail.starts_with('{') => resolve(&tail[1..end])?.map(|value| (value, end)),
+            _ => None,
+        };
+        match resolved {
+            Some((value, end)) => {
+                result.push(value);
+                rest = &tail[end + 1..];
+            }
+            None => {
+                if tail.starts_with('{') {
+                    kept += 1;
+                }
+                result.push(&tail[..1]);
+                rest = &tail[1..];
+            }
+        }
+    }
+    result.push(rest);
+    Ok(result)
+}
+
+struct Substitution {
+    fixtures: PathBuf,
+    tmpdir: Option<TempDir>,
+}
+
+impl Substitution {
+    /// Replace the placeholders in `arg`, leaving arguments that aren't UTF-8 alone.
+    fn apply(&mut self, arg: &OsStr) -> Result<OsString, String> {
+        match arg.to_str() {
+            Some(arg) => interpolate(arg, |placeholder| self.resolve(placeholder)),
+            None => Ok(arg.to_owned()),
+        }
+    }
+
+    /// The value of a known placeholder.
+    fn resolve(&mut self, placeholder: &str) -> Result<Option<OsString>, String> {
+        if placeholder == "tmpdir" {
+            if self.tmpdir.is_none() {
+                let dir = tempfile::Builder::new()
+                    .prefix("assert_cli-")
+                    .tempdir()
+                    .map_err(|e| format!("failed to create `{{tmpdir}}`: {}", e))?;
+                self.tmpdir = Some(dir);
+            }
+            let dir = self.tmpdir.as_ref().expect("created above");
+            return Ok(Some(dir.path().as_os_str().to_owned()));
+        }
+        if let Some(name) = placeholder.strip_prefix("fixture:") {
+            let path = self.fixtures.join(name);
+            if !path.exists() {
+                return Err(format!(
+                    "fixture `{}` not found at {}",
+                    name,
+                    path.display()
+                ));
+            }
+            return Ok(Some(path.into_os_string()));
+        }
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use failure::Fail;
+
+    fn substitution() -> Substitution {
+        Substitution {
+            fixtures: PathBuf::from("src"),
+            tmpdir: None,
+        }
+    }
+
+    #[test]
+    fn substitutes_placeholders() {
+        let mut substitution = substitution();
+        let arg = substitution.apply(OsStr::new("--config={fixture:lib.rs}"));
+        assert_eq!(arg.unwrap(), OsStr::new("--config=src/lib.rs"));
+
+        let first = substitution.apply(OsStr::new("{tmpdir}/a")).unwrap();
+        let second = substitution.apply(OsStr::new("{tmpdir}/b")).unwrap();
+        let tmpdir = substitution.tmpdir.as_ref().unwrap().path();
+        assert_eq!(first, tmpdir.join("a").into_os_string());
+        assert_eq!(second, tmpdir.join("b").into_os_string());
+    }
+
+    #[test]
+    fn escapes_braces() {
+        let arg = substitution().apply(OsStr::new("{{\"a\": 1}}"));
+        assert_eq!(arg.unwrap(), OsStr::new("{\"a\": 1}"));
+    }
+
+    #[test]
+    fn keeps_other_braces() {
+        let mut substitution = substitution();
+        for arg in &["{\"a\":{\"b\":1}}", "{print $1}", "{tempdir}", "a}b{", "{"] {
+            assert_eq!(
+                substitution.apply(OsStr::new(arg)).unwrap(),
+                OsStr::new(arg)
+            );
+        }
+        let arg = substitution.apply(OsStr::new("{{\"a\":{\"b\":1}}}"));
+        assert_eq!(arg.unwrap(), OsStr::new("{\"a\":{\"b\":1}}"));
+        assert!(substitution.tmpdir.is_none());
+    }
+
+    #[test]
+    fn missing_fixture_fails_execution() {
+        let err = CommandTemplate::command(&["cat"])
+            .fixtures("src")
+            .case(&["{fixture:missing.rs}"])
+            .execute()
+            .unwrap_err();
+        let message = Assert::format_causes(err.causes());
+        assert!(
+            message.starts_with("Assertion failed for `cat {fixture:missing.rs}`"),
+            "{}",
+            message
+        );
+        assert!(message.contains("with: fixture `missing.rs` not found at src"));
+    }
+
+    #[test]
+    fn cases_extend_env() {
+        let template = CommandTemplate::command(&["printenv"]).with_env(&[("A", "1"), ("B", "2")]);
+        template
+            .case::<&str>(&[])
+            .with_env(&[("B", "3"), ("C", "4")])
+            .stdout()
+            .is("A=1\nB=3\nC=4")
+            .unwrap();
+        template.assert().stdout().is("A=1\nB=2").unwrap();
+    }
+
+    #[test]
+    fn cases_dont_share_arguments() {
+        let template = CommandTemplate::command(&["echo"]).with_args(&["-n"]);
+        template.case(&["a"]).stdout().is("a").unwrap();
+        template.case(&["b"]).stdout().is("b").unwrap();
+    }
+}